@@ -0,0 +1,276 @@
+use super::config::ConfigBlock;
+
+/// Flattens `block` into the keys corosync exposes through cmap, e.g.
+/// `totem.token` or `nodelist.node.1.ring0_addr`, in document order. A block
+/// without a name is treated as the document root.
+///
+/// Repeated sections are indexed like corosync does: `interface` by its
+/// `linknumber` (or `ringnumber`), `node` by its position in the nodelist and
+/// `logger_subsys` by its `subsys`. Options in `uidgid` become
+/// `uidgid.uid.<name>` set to `1`. Values with several parameters are joined
+/// with spaces
+pub fn to_cmap(block: &ConfigBlock) -> Vec<(String, String)> {
+    entries(block)
+        .into_iter()
+        .map(|(key, value, _)| (key, value))
+        .collect()
+}
+
+/// Like `to_cmap`, but also returns the option every key comes from
+pub(crate) fn entries(block: &ConfigBlock) -> Vec<Entry<'_>> {
+    let mut pairs = vec![];
+    if block.name().is_empty() {
+        flatten(&mut pairs, "", block, &[]);
+    } else if block.is_option() {
+        pairs.push((block.name().to_string(), value(block), block));
+    } else {
+        flatten(&mut pairs, &format!("{}.", block.name()), block, &[]);
+    }
+    pairs
+}
+
+/// A cmap key and value with the option it comes from
+pub(crate) type Entry<'a> = (String, String, &'a ConfigBlock);
+
+/// Adds the inner blocks of `block` under `prefix`, leaving out the options
+/// named in `skip`
+fn flatten<'a>(pairs: &mut Vec<Entry<'a>>, prefix: &str, block: &'a ConfigBlock, skip: &[&str]) {
+    let mut nodes = 0;
+    for inner in block.inner() {
+        let key = format!("{}{}", prefix, inner.name());
+        if inner.is_option() {
+            if !skip.contains(&inner.name()) {
+                pairs.push((key, value(inner), inner));
+            }
+            continue;
+        }
+        match key.as_str() {
+            "totem.interface" => {
+                let link = option_value(inner, "linknumber")
+                    .or_else(|| option_value(inner, "ringnumber"))
+                    .unwrap_or("0");
+                let prefix = format!("{}.{}.", key, link);
+                flatten(pairs, &prefix, inner, &["linknumber", "ringnumber"]);
+            }
+            "nodelist.node" => {
+                flatten(pairs, &format!("{}.{}.", key, nodes), inner, &[]);
+                nodes += 1;
+            }
+            "logging.logger_subsys" => match option_value(inner, "subsys") {
+                Some(subsys) => flatten(pairs, &format!("{}.{}.", key, subsys), inner, &[]),
+                None => flatten(pairs, &format!("{}.", key), inner, &[]),
+            },
+            "uidgid" => {
+                for option in inner.options() {
+                    let key = format!("uidgid.{}.{}", option.name(), value(option));
+                    pairs.push((key, String::from("1"), option));
+                }
+            }
+            _ => flatten(pairs, &format!("{}.", key), inner, &[]),
+        }
+    }
+}
+
+fn value(option: &ConfigBlock) -> String {
+    (0..option.len())
+        .map(|i| option.get(i))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn option_value<'a>(section: &'a ConfigBlock, name: &str) -> Option<&'a str> {
+    section
+        .options()
+        .find(|o| o.name() == name)
+        .and_then(|o| o.get_opt(0))
+}
+
+/// Rebuilds a tree from cmap keys and values, undoing `to_cmap`. Indexed
+/// sections get their `linknumber` or `subsys` back as an option, nodes are
+/// kept in the order their indices first appear. Empty key segments are
+/// ignored
+pub fn from_cmap<I, K, V>(pairs: I) -> ConfigBlock
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut root = ConfigBlock::new(String::new(), vec![], vec![]);
+    // Indices of the nodes in the order their sections were created
+    let mut nodes: Vec<String> = vec![];
+    for (key, value) in pairs {
+        let parts: Vec<&str> = key.as_ref().split('.').filter(|p| !p.is_empty()).collect();
+        let value = value.as_ref();
+        match parts.as_slice() {
+            ["totem", "interface", link, name] => {
+                let totem = section_mut(&mut root, "totem");
+                let interface = keyed_section_mut(totem, "interface", "linknumber", link);
+                set_option(interface, name, value);
+            }
+            ["nodelist", "node", index, name] => {
+                let nodelist = section_mut(&mut root, "nodelist");
+                let i = match nodes.iter().position(|n| n == index) {
+                    Some(i) => i,
+                    None => {
+                        nodes.push(index.to_string());
+                        nodelist.add_block(ConfigBlock::section(
+                            String::from("node"),
+                            vec![],
+                            vec![],
+                        ));
+                        nodes.len() - 1
+                    }
+                };
+                let node = nodelist
+                    .inner_mut()
+                    .iter_mut()
+                    .filter(|b| b.is_section() && b.name() == "node")
+                    .nth(i);
+                if let Some(node) = node {
+                    set_option(node, name, value);
+                }
+            }
+            ["logging", "logger_subsys", subsys, name] => {
+                let logging = section_mut(&mut root, "logging");
+                let section = keyed_section_mut(logging, "logger_subsys", "subsys", subsys);
+                set_option(section, name, value);
+            }
+            ["uidgid", kind, id] => {
+                let uidgid = section_mut(&mut root, "uidgid");
+                uidgid.add_block(ConfigBlock::option(kind.to_string(), vec![id.to_string()]));
+            }
+            [path @ .., name] => {
+                let section = path
+                    .iter()
+                    .fold(&mut root, |section, part| section_mut(section, part));
+                section.add_block(ConfigBlock::option(
+                    name.to_string(),
+                    vec![value.to_string()],
+                ));
+            }
+            [] => {}
+        }
+    }
+    root
+}
+
+/// Returns the first inner section called `name`, creating it if needed
+fn section_mut<'a>(parent: &'a mut ConfigBlock, name: &str) -> &'a mut ConfigBlock {
+    keyed_section_mut_by(parent, name, |_| true, |_| {})
+}
+
+/// Returns the inner section called `name` whose option `key` is `id`,
+/// creating it with that option if needed
+fn keyed_section_mut<'a>(
+    parent: &'a mut ConfigBlock,
+    name: &str,
+    key: &str,
+    id: &str,
+) -> &'a mut ConfigBlock {
+    keyed_section_mut_by(
+        parent,
+        name,
+        |b| option_value(b, key) == Some(id),
+        |b| b.add_block(ConfigBlock::option(key.to_string(), vec![id.to_string()])),
+    )
+}
+
+fn keyed_section_mut_by<'a, F, G>(
+    parent: &'a mut ConfigBlock,
+    name: &str,
+    matches: F,
+    init: G,
+) -> &'a mut ConfigBlock
+where
+    F: Fn(&ConfigBlock) -> bool,
+    G: FnOnce(&mut ConfigBlock),
+{
+    let existing = parent
+        .inner()
+        .iter()
+        .position(|b| b.is_section() && b.name() == name && matches(b));
+    let i = match existing {
+        Some(i) => i,
+        None => {
+            let mut section = ConfigBlock::section(name.to_string(), vec![], vec![]);
+            init(&mut section);
+            parent.add_block(section);
+            parent.inner().len() - 1
+        }
+    };
+    &mut parent.inner_mut()[i]
+}
+
+/// Sets the option `name` of `section`, replacing an existing value so that
+/// the keys indexing a section are not repeated
+fn set_option(section: &mut ConfigBlock, name: &str, value: &str) {
+    let existing = section
+        .inner_mut()
+        .iter_mut()
+        .find(|b| b.is_option() && b.name() == name);
+    match existing {
+        Some(option) => *option.params_mut() = vec![value.to_string()],
+        None => section.add_block(ConfigBlock::option(
+            name.to_string(),
+            vec![value.to_string()],
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn indexes_repeated_sections_like_corosync() {
+        let block = ConfigBlock::new(
+            String::new(),
+            vec![],
+            vec![
+                ConfigBlock::new(
+                    String::from("totem"),
+                    vec![],
+                    vec![
+                        ConfigBlock::option(String::from("token"), vec![String::from("3000")]),
+                        ConfigBlock::new(
+                            String::from("interface"),
+                            vec![],
+                            vec![
+                                ConfigBlock::option(
+                                    String::from("linknumber"),
+                                    vec![String::from("1")],
+                                ),
+                                ConfigBlock::option(
+                                    String::from("knet_link_priority"),
+                                    vec![String::from("2")],
+                                ),
+                            ],
+                        ),
+                    ],
+                ),
+                ConfigBlock::new(
+                    String::from("uidgid"),
+                    vec![],
+                    vec![ConfigBlock::option(
+                        String::from("uid"),
+                        vec![String::from("hacluster")],
+                    )],
+                ),
+            ],
+        );
+        let pairs = to_cmap(&block);
+        let expected = vec![
+            ("totem.token", "3000"),
+            ("totem.interface.1.knet_link_priority", "2"),
+            ("uidgid.uid.hacluster", "1"),
+        ];
+        assert_eq!(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(from_cmap(pairs), block);
+    }
+}
@@ -0,0 +1,279 @@
+use super::cmap;
+use super::config::ConfigBlock;
+use super::error::{Error, ErrorType, Result};
+use super::span::Span;
+use std::fmt;
+
+/// The type of a cmap value, as printed in parentheses by `corosync-cmapctl`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ValueType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    Float,
+    Double,
+    Str,
+    Binary,
+}
+
+impl ValueType {
+    /// Returns the name `corosync-cmapctl` prints, e.g. `u32`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::U8 => "u8",
+            ValueType::U16 => "u16",
+            ValueType::U32 => "u32",
+            ValueType::U64 => "u64",
+            ValueType::I8 => "i8",
+            ValueType::I16 => "i16",
+            ValueType::I32 => "i32",
+            ValueType::I64 => "i64",
+            ValueType::Float => "flt",
+            ValueType::Double => "dbl",
+            ValueType::Str => "str",
+            ValueType::Binary => "bin",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ValueType> {
+        let all = [
+            ValueType::U8,
+            ValueType::U16,
+            ValueType::U32,
+            ValueType::U64,
+            ValueType::I8,
+            ValueType::I16,
+            ValueType::I32,
+            ValueType::I64,
+            ValueType::Float,
+            ValueType::Double,
+            ValueType::Str,
+            ValueType::Binary,
+        ];
+        all.iter().copied().find(|t| t.as_str() == name)
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A line of `corosync-cmapctl` output, e.g. `totem.token (u32) = 3000`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CmapEntry {
+    pub key: String,
+    pub value_type: ValueType,
+    /// The value as printed
+    pub value: String,
+    /// Where the key was found
+    pub key_span: Span,
+    /// Where the value was found. Empty for an empty string
+    pub value_span: Span,
+}
+
+/// The output of `corosync-cmapctl`, one entry per line in the order printed
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CmapDump {
+    entries: Vec<CmapEntry>,
+}
+
+impl CmapDump {
+    /// Parses `corosync-cmapctl` output. Blank lines are skipped, any other
+    /// line which is not `key (type) = value` is an error
+    pub fn parse(input: &str) -> Result<CmapDump> {
+        let mut entries = vec![];
+        let mut offset = 0;
+        for (i, text) in input.split('\n').enumerate() {
+            let line = i as u32 + 1;
+            let start = offset;
+            offset += text.len() + 1;
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if text.trim().is_empty() {
+                continue;
+            }
+            entries.push(parse_line(text, start, line)?);
+        }
+        Ok(CmapDump { entries })
+    }
+
+    /// Returns all entries in the order printed
+    pub fn entries(&self) -> &[CmapEntry] {
+        &self.entries
+    }
+
+    /// Returns the entry for `key`
+    pub fn get(&self, key: &str) -> Option<&CmapEntry> {
+        self.entries.iter().find(|e| e.key == key)
+    }
+}
+
+fn parse_line(text: &str, start: usize, line: u32) -> Result<CmapEntry> {
+    let indent = text.len() - text.trim_start().len();
+    let rest = &text[indent..];
+    let key_len = match rest.find(" (") {
+        Some(0) | None => return fail(text, indent, line, "missing key or type", "key (type)"),
+        Some(n) => n,
+    };
+    let key = &rest[..key_len];
+    let after_key = indent + key_len + 2;
+    let type_len = match text[after_key..].find(')') {
+        Some(n) => n,
+        None => return fail(text, after_key, line, "unterminated type", ")"),
+    };
+    let name = &text[after_key..after_key + type_len];
+    let value_type = match ValueType::from_name(name) {
+        Some(t) => t,
+        None => {
+            let message = format!("unknown type `{}`", name);
+            return fail(
+                text,
+                after_key,
+                line,
+                &message,
+                "u8 to u64, i8 to i64, flt, dbl, str or bin",
+            );
+        }
+    };
+    let after_type = after_key + type_len + 1;
+    let rest = &text[after_type..];
+    let value_start = match rest.trim_start().strip_prefix('=') {
+        Some(value) => text.len() - value.strip_prefix(' ').unwrap_or(value).len(),
+        None => return fail(text, after_type, line, "missing `=`", "="),
+    };
+    Ok(CmapEntry {
+        key: key.to_string(),
+        value_type,
+        value: text[value_start..].to_string(),
+        key_span: span(text, start, line, indent, indent + key_len),
+        value_span: span(text, start, line, value_start, text.len()),
+    })
+}
+
+/// Returns the span of the bytes `from..to` of the line `text`, which starts
+/// at the byte offset `start` of the input
+fn span(text: &str, start: usize, line: u32, from: usize, to: usize) -> Span {
+    let col = text[..from].chars().count() as u32 + 1;
+    Span::new(start + from, start + to, line, col)
+}
+
+fn fail<T>(text: &str, at: usize, line: u32, message: &str, expected: &'static str) -> Result<T> {
+    let col = text[..at].chars().count() as u32 + 1;
+    let etype = ErrorType::InvalidDump(message.to_string());
+    Err(Error::new(line, col, etype, Some(expected)))
+}
+
+/// A key whose runtime value does not match the config file
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Difference {
+    /// Set in both, to different values
+    Changed {
+        key: String,
+        file: String,
+        /// Where the option was found in the file, if it was parsed
+        file_span: Option<Span>,
+        runtime: CmapEntry,
+    },
+    /// Only known at runtime, e.g. a default or a `runtime.*` statistic
+    RuntimeOnly(CmapEntry),
+    /// Only set in the file
+    FileOnly {
+        key: String,
+        value: String,
+        file_span: Option<Span>,
+    },
+}
+
+impl Difference {
+    /// Returns the cmap key which differs
+    pub fn key(&self) -> &str {
+        match self {
+            Difference::Changed { key, .. } => key,
+            Difference::RuntimeOnly(entry) => &entry.key,
+            Difference::FileOnly { key, .. } => key,
+        }
+    }
+}
+
+/// Compares a config file with the runtime state dumped by
+/// `corosync-cmapctl`. Keys of the file are named as by `ConfigBlock::to_cmap`.
+/// If the file sets a key more than once, the last value counts like in
+/// corosync. Returns the changed keys and those only in the file in file
+/// order, followed by those only known at runtime in dump order
+pub fn compare(file: &ConfigBlock, dump: &CmapDump) -> Vec<Difference> {
+    let entries = cmap::entries(file);
+    let mut differences = vec![];
+    for (i, (key, value, option)) in entries.iter().enumerate() {
+        if entries[i + 1..].iter().any(|(k, _, _)| k == key) {
+            continue;
+        }
+        let file_span = option.span();
+        match dump.get(key) {
+            Some(entry) if entry.value == *value => {}
+            Some(entry) => differences.push(Difference::Changed {
+                key: key.clone(),
+                file: value.clone(),
+                file_span,
+                runtime: entry.clone(),
+            }),
+            None => differences.push(Difference::FileOnly {
+                key: key.clone(),
+                value: value.clone(),
+                file_span,
+            }),
+        }
+    }
+    for entry in dump.entries() {
+        if entries.iter().all(|(key, _, _)| *key != entry.key) {
+            differences.push(Difference::RuntimeOnly(entry.clone()));
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_typed_lines() {
+        let dump = CmapDump::parse(
+            "totem.token (u32) = 3000\r\ntotem.cluster_name (str) = prod cluster\n\nlogging.debug (str) = \n",
+        )
+        .unwrap();
+        let entries = dump.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].value_type, ValueType::U32);
+        assert_eq!(entries[0].value, "3000");
+        assert_eq!(entries[0].key_span, Span::new(0, 11, 1, 1));
+        assert_eq!(entries[0].value_span, Span::new(20, 24, 1, 21));
+        assert_eq!(entries[1].value, "prod cluster");
+        assert_eq!(entries[1].key_span.line, 2);
+        assert_eq!(entries[2].value, "");
+        assert_eq!(entries[2].value_span.line, 4);
+    }
+
+    #[test]
+    fn fails_on_malformed_lines() {
+        for (input, col) in &[
+            ("totem.token = 3000", 1),
+            ("totem.token (u32 = 3000", 14),
+            ("totem.token (u128) = 3000", 14),
+            ("totem.token (u32) 3000", 18),
+        ] {
+            let err = CmapDump::parse(input).unwrap_err();
+            assert!(
+                matches!(err.error_type(), ErrorType::InvalidDump(_)),
+                "{}",
+                input
+            );
+            assert_eq!((err.line(), err.col()), (1, *col), "{}", input);
+        }
+    }
+}
@@ -1,33 +1,194 @@
+use super::cmap;
+use super::effective::{self, CorosyncVersion, EffectiveConfig};
+use super::error::Result;
+use super::query::{Query, QueryMatch};
+use super::span::Span;
+use super::writer::{self, FormatOptions};
 use std::iter;
 use std::slice;
 
 /// Represents a block in the config file or the document root
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct ConfigBlock {
     name: String,
     params: Vec<String>,
     inner: Vec<ConfigBlock>,
+    /// Whether the block is a section even without inner blocks
+    section: bool,
+    pub(crate) positions: Positions,
+    pub(crate) broken: bool,
 }
 
+/// Where the parts of a block were found in the source text. Blocks which
+/// were not produced by the parser have no positions
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Positions {
+    pub(crate) name: Option<Span>,
+    /// `None` for parameters replaced after parsing
+    pub(crate) params: Vec<Option<Span>>,
+    pub(crate) open_brace: Option<Span>,
+    pub(crate) close_brace: Option<Span>,
+}
+
+/// Whether a block is a section or an option
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BlockKind {
+    /// `name { ... }`, possibly empty
+    Section,
+    /// `key: value`, possibly without a value
+    Option,
+}
+
+/// A block seen by its kind, as returned by `ConfigBlock::as_node`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigNode<'a> {
+    Section {
+        name: &'a str,
+        children: &'a [ConfigBlock],
+    },
+    /// The value is the first parameter. Options with more than one are rare
+    /// and need `ConfigBlock::get_opt`
+    Option {
+        key: &'a str,
+        value: Option<&'a str>,
+    },
+}
+
+/// Blocks are compared by kind, name, parameters and inner blocks. Where they
+/// were found in the source and whether they are broken is not taken into
+/// account
+impl PartialEq for ConfigBlock {
+    fn eq(&self, other: &ConfigBlock) -> bool {
+        self.kind() == other.kind()
+            && self.name == other.name
+            && self.params == other.params
+            && self.inner == other.inner
+    }
+}
+
+impl Eq for ConfigBlock {}
+
 impl ConfigBlock {
+    /// Creates a block whose kind follows from its contents: a section if it
+    /// has inner blocks and an option otherwise. The kind is fixed here, so
+    /// the block stays a section when its inner blocks are removed later. Use
+    /// `section` for an empty section
     pub fn new(name: String, params: Vec<String>, inner: Vec<ConfigBlock>) -> ConfigBlock {
         ConfigBlock {
-            name: name,
-            params: params,
-            inner: inner,
+            name,
+            params,
+            section: !inner.is_empty(),
+            inner,
+            positions: Positions::default(),
+            broken: false,
         }
     }
 
+    /// Creates a section, which stays one even if it has no inner blocks
+    pub fn section(name: String, params: Vec<String>, inner: Vec<ConfigBlock>) -> ConfigBlock {
+        let mut block = ConfigBlock::new(name, params, inner);
+        block.section = true;
+        block
+    }
+
+    /// Creates an option with its values
+    pub fn option(key: String, values: Vec<String>) -> ConfigBlock {
+        ConfigBlock::new(key, values, vec![])
+    }
+
+    /// Returns whether the block is a section or an option. A block with
+    /// inner blocks is always a section
+    pub fn kind(&self) -> BlockKind {
+        if self.section || !self.inner.is_empty() {
+            BlockKind::Section
+        } else {
+            BlockKind::Option
+        }
+    }
+
+    /// Turns the block into a section or an option. Inner blocks have to be
+    /// removed for it to become an option
+    pub fn set_kind(&mut self, kind: BlockKind) {
+        self.section = kind == BlockKind::Section;
+    }
+
+    /// Returns true if the block is a section
+    pub fn is_section(&self) -> bool {
+        self.kind() == BlockKind::Section
+    }
+
+    /// Returns true if the block is an option
+    pub fn is_option(&self) -> bool {
+        self.kind() == BlockKind::Option
+    }
+
+    /// Returns the block by its kind, e.g. for matching on it
+    pub fn as_node(&self) -> ConfigNode<'_> {
+        match self.kind() {
+            BlockKind::Section => ConfigNode::Section {
+                name: &self.name,
+                children: &self.inner,
+            },
+            BlockKind::Option => ConfigNode::Option {
+                key: &self.name,
+                value: self.get_opt(0),
+            },
+        }
+    }
+
+    /// Returns an iterator of all inner sections
+    pub fn sections(&self) -> impl Iterator<Item = &ConfigBlock> + '_ {
+        self.inner.iter().filter(|c| c.is_section())
+    }
+
+    /// Returns an iterator of all inner options
+    pub fn options(&self) -> impl Iterator<Item = &ConfigBlock> + '_ {
+        self.inner.iter().filter(|c| c.is_option())
+    }
+
+    /// Returns true if a recovering parse found an error in this block, e.g.
+    /// an unexpected token in its parameters or a missing closing brace
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
     /// Adds a new sub block. This probably isn't very useful for you
     pub fn add_block(&mut self, block: ConfigBlock) {
         self.inner.push(block);
     }
 
+    /// Inserts a sub block at position `index` among the inner blocks. Panics
+    /// if the index is higher than the number of inner blocks
+    pub fn insert_at(&mut self, index: usize, block: ConfigBlock) {
+        self.inner.insert(index, block);
+    }
+
+    /// Removes all inner blocks with the specified name and returns them
+    pub fn remove_matching(&mut self, name: &str) -> Vec<ConfigBlock> {
+        let (removed, kept) = self.inner.drain(..).partition(|c| c.name == name);
+        self.inner = kept;
+        removed
+    }
+
+    /// Returns all inner config options for editing
+    pub fn inner_mut(&mut self) -> &mut Vec<ConfigBlock> {
+        &mut self.inner
+    }
+
+    /// Returns an iterator of all inner config options with the specified name
+    /// for editing
+    pub fn matching_mut<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a mut ConfigBlock> + 'a {
+        self.inner.iter_mut().filter(move |c| c.name == name)
+    }
+
     /// Returns an iterator of all inner config options with the specified name
     pub fn matching<'a>(&'a self, name: &'a str) -> ConfigIter<'a> {
         ConfigIter {
             it: self.inner.iter(),
-            name: name,
+            name,
         }
     }
 
@@ -37,7 +198,7 @@ impl ConfigBlock {
     }
 
     /// Returns all inner config options
-    pub fn inner<'a>(&'a self) -> &'a Vec<ConfigBlock> {
+    pub fn inner(&self) -> &Vec<ConfigBlock> {
         &self.inner
     }
 
@@ -46,11 +207,37 @@ impl ConfigBlock {
         self.params.len()
     }
 
+    /// Returns true if the block has no parameters
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
     /// Returns a parameter. Panics if the parameter index is too high
     pub fn get(&self, i: usize) -> &str {
         &self.params[i]
     }
 
+    /// Sets a parameter, appending it if `i` equals the parameter count.
+    /// Panics if the parameter index is higher than that. The source span
+    /// of the replaced parameter is dropped
+    pub fn set_param(&mut self, i: usize, value: &str) {
+        if let Some(span) = self.positions.params.get_mut(i) {
+            *span = None;
+        }
+        if i == self.params.len() {
+            self.params.push(value.to_string());
+        } else {
+            self.params[i] = value.to_string();
+        }
+    }
+
+    /// Returns all parameters for editing. Source spans of the parameters are
+    /// dropped, as they could no longer be matched to the edited values
+    pub fn params_mut(&mut self) -> &mut Vec<String> {
+        self.positions.params.clear();
+        &mut self.params
+    }
+
     /// Returns a parameter. Returns None if the index is too high
     pub fn get_opt(&self, i: usize) -> Option<&str> {
         if i < self.params.len() {
@@ -60,18 +247,111 @@ impl ConfigBlock {
         }
     }
 
-    pub fn path<'a>(&'a self, keys: Vec<&'a str>) -> Option<&str> {
-        let last_config_block =
-            keys.iter()
-                .fold(Some(self), |accumulator, key| match accumulator {
-                    Some(config_block) => config_block.matching(key).nth(0),
-                    None => None,
-                });
+    /// Returns the source span of the whole block, from the start of its name
+    /// to its closing brace or last parameter
+    pub fn span(&self) -> Option<Span> {
+        let start = self.positions.name?;
+        let end = self
+            .positions
+            .close_brace
+            .or(self.positions.open_brace)
+            .or(self.positions.params.iter().rev().flatten().next().copied())
+            .unwrap_or(start);
+        Some(start.to(end))
+    }
+
+    /// Returns the source span of the block name
+    pub fn name_span(&self) -> Option<Span> {
+        self.positions.name
+    }
+
+    /// Returns the source span of a parameter. Returns None if the index is too high
+    pub fn param_span(&self, i: usize) -> Option<Span> {
+        self.positions.params.get(i).copied().flatten()
+    }
+
+    /// Returns the source span of the opening brace, if the block has one
+    pub fn open_brace_span(&self) -> Option<Span> {
+        self.positions.open_brace
+    }
+
+    /// Returns the source span of the closing brace, if the block has one
+    pub fn close_brace_span(&self) -> Option<Span> {
+        self.positions.close_brace
+    }
+
+    /// Flattens the block into cmap keys and values, e.g.
+    /// `("nodelist.node.1.ring0_addr", "10.0.0.2")`. See `cmap::to_cmap`
+    pub fn to_cmap(&self) -> Vec<(String, String)> {
+        cmap::to_cmap(self)
+    }
+
+    /// Fills in the defaults `version` of corosync uses for everything the
+    /// config leaves out. See `effective::effective`
+    pub fn effective(&self, version: CorosyncVersion) -> EffectiveConfig {
+        effective::effective(self, version)
+    }
+
+    /// Rebuilds a tree from cmap keys and values. See `cmap::from_cmap`
+    pub fn from_cmap<I, K, V>(pairs: I) -> ConfigBlock
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        cmap::from_cmap(pairs)
+    }
+
+    /// Writes the block back to corosync.conf syntax. A block without a name
+    /// is treated as the document root. See `writer::write`
+    pub fn to_corosync_string(&self, options: &FormatOptions) -> Result<String> {
+        writer::write(self, options)
+    }
+
+    /// Follows `keys` through the tree, taking the first block with a matching
+    /// name on every level, and returns the first parameter of the last block.
+    /// Returns None if a block is missing or has no parameters
+    pub fn path<'a>(&'a self, keys: Vec<&'a str>) -> Option<&'a str> {
+        keys.iter()
+            .try_fold(self, |config_block, key| config_block.matching(key).next())
+            .and_then(|config_block| config_block.get_opt(0))
+    }
+
+    /// Returns all blocks matched by `query`, e.g. `nodelist.node[nodeid=2].ring0_addr`.
+    /// See `Query` for the syntax. Fails only if the query is malformed
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch<'_>>> {
+        Ok(Query::parse(query)?.matches(self))
+    }
+
+    /// Follows `keys` through the tree, taking the first block with a matching
+    /// name on every level, and returns the last block for editing
+    pub fn path_mut(&mut self, keys: &[&str]) -> Option<&mut ConfigBlock> {
+        keys.iter().try_fold(self, |config_block, key| {
+            config_block.inner.iter_mut().find(|c| c.name == *key)
+        })
+    }
 
-        match last_config_block {
-            Some(config_block) => Some(config_block.get(0)),
-            None => None,
+    /// Sets the value of the option at `keys`, e.g. `&["totem", "token"]`,
+    /// replacing all its parameters. Missing sections and the option itself
+    /// are created on the way. Returns the option
+    pub fn set_path(&mut self, keys: &[&str], value: &str) -> &mut ConfigBlock {
+        let mut block = self;
+        for (n, key) in keys.iter().enumerate() {
+            let i = match block.inner.iter().position(|c| c.name == *key) {
+                Some(i) => i,
+                None if n + 1 == keys.len() => {
+                    block.add_block(ConfigBlock::option(key.to_string(), vec![]));
+                    block.inner.len() - 1
+                }
+                None => {
+                    block.add_block(ConfigBlock::section(key.to_string(), vec![], vec![]));
+                    block.inner.len() - 1
+                }
+            };
+            block = &mut block.inner[i];
         }
+        *block.params_mut() = vec![value.to_string()];
+        block
     }
 }
 
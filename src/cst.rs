@@ -0,0 +1,456 @@
+use super::config::ConfigBlock;
+use super::error::Result;
+use super::lexer::Lexer;
+use super::parser::{self, Duplicates, ParseOptions};
+use super::span::Span;
+use super::writer;
+use std::fmt;
+
+/// A lossless concrete syntax tree of a config file. Comments, blank lines,
+/// indentation and ordering are kept as trivia attached to the nodes, so
+/// writing an unmodified document gives back the exact input and editing a
+/// value only changes the text of that value
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Document {
+    children: Vec<Node>,
+    trailing: String,
+}
+
+/// An option or section in a `Document`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Node {
+    /// Comment lines, blank lines and indentation before the name
+    leading: String,
+    name: String,
+    params: Vec<Param>,
+    body: Option<Body>,
+    /// Rest of the line after the node, including the line break
+    trailing: String,
+    /// Whether values are the rest of their line, see `ParseOptions::line_values`
+    line_values: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Param {
+    /// Text between the previous token and the parameter, e.g. `: `
+    before: String,
+    /// The parameter as written, including quotes
+    raw: String,
+    value: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Body {
+    /// Text between the last parameter or the name and the opening brace
+    before_open: String,
+    /// Rest of the line after the opening brace, including the line break
+    open_trailing: String,
+    children: Vec<Node>,
+    /// Trivia between the last inner node and the closing brace
+    before_close: String,
+}
+
+impl Document {
+    /// Parses a config file the way corosync reads it, keeping all trivia.
+    /// See `ParseOptions::corosync`
+    pub fn parse(input: &str) -> Result<Document> {
+        Document::parse_with(input, &ParseOptions::corosync())
+    }
+
+    /// Like `parse`, but with explicit parsing options. The duplicate key
+    /// policy is not applied, as every option in the text has to be a node
+    pub fn parse_with(input: &str, options: &ParseOptions) -> Result<Document> {
+        let options = &ParseOptions {
+            duplicates: Duplicates::Keep,
+            ..options.clone()
+        };
+        let root = parser::run_with(Lexer::with_options(input, options), options)?;
+        let (_, children, trailing) = build_children(
+            input,
+            root.inner(),
+            0,
+            input.len(),
+            false,
+            options.line_values,
+        );
+        Ok(Document { children, trailing })
+    }
+
+    /// Returns all top level nodes
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// Returns all top level nodes for editing
+    pub fn children_mut(&mut self) -> &mut [Node] {
+        &mut self.children
+    }
+
+    /// Follows `path` through the tree, taking the first node with a matching
+    /// name on every level
+    pub fn node(&self, path: &[&str]) -> Option<&Node> {
+        let (first, rest) = path.split_first()?;
+        let node = self.children.iter().find(|n| n.name == *first)?;
+        rest.iter().try_fold(node, |node, key| {
+            node.children().iter().find(|n| n.name == *key)
+        })
+    }
+
+    /// Like `node`, but returns the node for editing
+    pub fn node_mut(&mut self, path: &[&str]) -> Option<&mut Node> {
+        let (first, rest) = path.split_first()?;
+        let node = self.children.iter_mut().find(|n| n.name == *first)?;
+        rest.iter().try_fold(node, |node, key| {
+            node.children_mut().iter_mut().find(|n| n.name == *key)
+        })
+    }
+
+    /// Converts the document into a plain tree, dropping all trivia
+    pub fn to_config_block(&self) -> ConfigBlock {
+        ConfigBlock::new(
+            String::new(),
+            vec![],
+            self.children.iter().map(Node::to_config_block).collect(),
+        )
+    }
+}
+
+impl Node {
+    /// Returns the name of the option key
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the parameter values, with quotes and escapes resolved
+    pub fn values(&self) -> Vec<&str> {
+        self.params.iter().map(|p| p.value.as_str()).collect()
+    }
+
+    /// Returns true if the node is a section written with braces
+    pub fn is_section(&self) -> bool {
+        self.body.is_some()
+    }
+
+    /// Returns the inner nodes of a section
+    pub fn children(&self) -> &[Node] {
+        match self.body {
+            Some(ref body) => &body.children,
+            None => &[],
+        }
+    }
+
+    /// Returns the inner nodes of a section for editing
+    pub fn children_mut(&mut self) -> &mut [Node] {
+        match self.body {
+            Some(ref mut body) => &mut body.children,
+            None => &mut [],
+        }
+    }
+
+    /// Returns the comments, blank lines and indentation before the node
+    pub fn leading_trivia(&self) -> &str {
+        &self.leading
+    }
+
+    /// Returns the rest of the line after the node, e.g. a trailing comment
+    pub fn trailing_trivia(&self) -> &str {
+        &self.trailing
+    }
+
+    /// Replaces the parameters with a single value
+    pub fn set_value(&mut self, value: &str) {
+        self.set_values(&[value]);
+    }
+
+    /// Replaces the parameters. The separator before the first parameter and
+    /// all surrounding trivia are kept, values are quoted where necessary.
+    /// Values of a document read with line values are written verbatim, so
+    /// they must not contain line breaks
+    pub fn set_values(&mut self, values: &[&str]) {
+        let first_before = match self.params.first() {
+            Some(p) => p.before.clone(),
+            None if self.is_section() => String::from(" "),
+            None => String::from(": "),
+        };
+        let section = self.is_section();
+        let verbatim = self.line_values && !section;
+        self.params = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Param {
+                before: if i == 0 {
+                    first_before.clone()
+                } else {
+                    String::from(" ")
+                },
+                raw: if verbatim {
+                    value.to_string()
+                } else if section {
+                    writer::quote(value)
+                } else {
+                    writer::quote_value(value)
+                },
+                value: value.to_string(),
+            })
+            .collect();
+    }
+
+    /// Converts the node into a plain tree, dropping all trivia
+    pub fn to_config_block(&self) -> ConfigBlock {
+        let params = self.params.iter().map(|p| p.value.clone()).collect();
+        if self.is_section() {
+            let children = self.children().iter().map(Node::to_config_block).collect();
+            ConfigBlock::section(self.name.clone(), params, children)
+        } else {
+            ConfigBlock::option(self.name.clone(), params)
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.children {
+            write!(f, "{}", node)?;
+        }
+        f.write_str(&self.trailing)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.leading)?;
+        f.write_str(&self.name)?;
+        for param in &self.params {
+            f.write_str(&param.before)?;
+            f.write_str(&param.raw)?;
+        }
+        if let Some(ref body) = self.body {
+            f.write_str(&body.before_open)?;
+            f.write_str("{")?;
+            f.write_str(&body.open_trailing)?;
+            for node in &body.children {
+                write!(f, "{}", node)?;
+            }
+            f.write_str(&body.before_close)?;
+            f.write_str("}")?;
+        }
+        f.write_str(&self.trailing)
+    }
+}
+
+/// Splits the trivia between two tokens into the rest of the first line,
+/// which belongs to whatever came before, and the remainder
+fn split_trivia(trivia: &str) -> (String, String) {
+    match trivia.find('\n') {
+        Some(i) => (trivia[..i + 1].to_string(), trivia[i + 1..].to_string()),
+        None => (trivia.to_string(), String::new()),
+    }
+}
+
+/// Builds the nodes for `blocks`, which lie between the byte offsets `start`
+/// and `end`. If `opened` is set, the rest of the first line belongs to an
+/// opening brace and is returned separately. Also returns the nodes and the
+/// trivia after the last node
+fn build_children(
+    input: &str,
+    blocks: &[ConfigBlock],
+    start: usize,
+    end: usize,
+    opened: bool,
+    line_values: bool,
+) -> (String, Vec<Node>, String) {
+    let mut first = String::new();
+    let mut nodes: Vec<Node> = vec![];
+    let mut cursor = start;
+    for block in blocks {
+        let name = block.name_span().unwrap_or_default();
+        let gap = &input[cursor..name.start];
+        let (trailing, leading) = match nodes.last_mut() {
+            Some(_) => split_trivia(gap),
+            None if opened => split_trivia(gap),
+            None => (String::new(), gap.to_string()),
+        };
+        match nodes.last_mut() {
+            Some(prev) => prev.trailing = trailing,
+            None => first = trailing,
+        }
+        let (node, node_end) = build_node(input, block, name, leading, line_values);
+        nodes.push(node);
+        cursor = node_end;
+    }
+    let gap = &input[cursor..end];
+    let rest = match nodes.last_mut() {
+        Some(prev) => {
+            let (trailing, rest) = split_trivia(gap);
+            prev.trailing = trailing;
+            rest
+        }
+        None if opened => {
+            let (trailing, rest) = split_trivia(gap);
+            first = trailing;
+            rest
+        }
+        None => gap.to_string(),
+    };
+    (first, nodes, rest)
+}
+
+fn build_node(
+    input: &str,
+    block: &ConfigBlock,
+    name: Span,
+    leading: String,
+    line_values: bool,
+) -> (Node, usize) {
+    let mut cursor = name.end;
+    let mut params = vec![];
+    for i in 0..block.len() {
+        let span = block.param_span(i).unwrap_or_default();
+        params.push(Param {
+            before: input[cursor..span.start].to_string(),
+            raw: input[span.start..span.end].to_string(),
+            value: block.get(i).to_string(),
+        });
+        cursor = span.end;
+    }
+
+    let body = match (block.open_brace_span(), block.close_brace_span()) {
+        (Some(open), Some(close)) => {
+            let (open_trailing, children, before_close) = build_children(
+                input,
+                block.inner(),
+                open.end,
+                close.start,
+                true,
+                line_values,
+            );
+            let body = Body {
+                before_open: input[cursor..open.start].to_string(),
+                open_trailing,
+                children,
+                before_close,
+            };
+            cursor = close.end;
+            Some(body)
+        }
+        _ => None,
+    };
+
+    let node = Node {
+        leading,
+        name: input[name.start..name.end].to_string(),
+        params,
+        body,
+        trailing: String::new(),
+        line_values,
+    };
+    (node, cursor)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "# Please read the corosync.conf.5 manual page
+totem {
+\tversion: 2
+
+\t# Time in ms
+\ttoken: 3000 # raised for VMs
+\tcluster_name: \"prod cluster\"
+\tinterface {
+\t\tlinknumber: 0
+\t}
+}
+
+/* quorum */
+quorum {
+    provider: corosync_votequorum
+}
+";
+
+    #[test]
+    fn writes_unmodified_document_verbatim() {
+        let doc = Document::parse(EXAMPLE).unwrap();
+        assert_eq!(doc.to_string(), EXAMPLE);
+    }
+
+    #[test]
+    fn writes_empty_and_comment_only_documents_verbatim() {
+        for input in &["", "\n\n", "# just a comment\n", "a: b\n", "empty {\n}"] {
+            assert_eq!(Document::parse(input).unwrap().to_string(), *input);
+        }
+    }
+
+    #[test]
+    fn attaches_trivia_to_nodes() {
+        let doc = Document::parse_with(EXAMPLE, &ParseOptions::default()).unwrap();
+        let token = doc.node(&["totem", "token"]).unwrap();
+        assert_eq!(token.leading_trivia(), "\n\t# Time in ms\n\t");
+        assert_eq!(token.trailing_trivia(), " # raised for VMs\n");
+        assert_eq!(token.values(), vec!["3000"]);
+    }
+
+    #[test]
+    fn editing_a_value_only_changes_that_value() {
+        let mut doc = Document::parse_with(EXAMPLE, &ParseOptions::default()).unwrap();
+        doc.node_mut(&["totem", "token"]).unwrap().set_value("5000");
+        doc.node_mut(&["totem", "cluster_name"])
+            .unwrap()
+            .set_value("test cluster");
+        assert_eq!(
+            doc.to_string(),
+            EXAMPLE
+                .replace("3000", "5000")
+                .replace("prod cluster", "test cluster")
+        );
+    }
+
+    #[test]
+    fn keeps_ipv6_addresses_unquoted() {
+        let mut doc = Document::parse("node {\n\tring0_addr: fe80::1\n}\n").unwrap();
+        let addr = doc.node_mut(&["node", "ring0_addr"]).unwrap();
+        assert_eq!(addr.values(), vec!["fe80::1"]);
+        addr.set_value("2001:db8::10");
+        assert_eq!(doc.to_string(), "node {\n\tring0_addr: 2001:db8::10\n}\n");
+    }
+
+    #[test]
+    fn converts_to_config_block() {
+        let doc = Document::parse_with(EXAMPLE, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            doc.to_config_block(),
+            parser::run(Lexer::new(EXAMPLE)).unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_duplicate_keys_as_nodes() {
+        let input = "a: 1\nb: 2\na: 3\n";
+        for duplicates in [Duplicates::Last, Duplicates::First, Duplicates::Error] {
+            let options = ParseOptions {
+                duplicates,
+                ..ParseOptions::default()
+            };
+            let mut doc = Document::parse_with(input, &options).unwrap();
+            assert_eq!(doc.to_string(), input);
+            let names: Vec<&str> = doc.children().iter().map(|n| n.name()).collect();
+            assert_eq!(names, vec!["a", "b", "a"]);
+            doc.children_mut()[2].set_value("4");
+            assert_eq!(doc.to_string(), "a: 1\nb: 2\na: 4\n");
+        }
+    }
+
+    #[test]
+    fn reads_values_like_corosync_by_default() {
+        let input = "logging {\n\t# where to log\n\tlogfile: /var/log//cluster.log\n}\n";
+        let mut doc = Document::parse(input).unwrap();
+        let logfile = doc.node_mut(&["logging", "logfile"]).unwrap();
+        assert_eq!(logfile.values(), vec!["/var/log//cluster.log"]);
+        logfile.set_value("/srv/new//cluster.log");
+        assert_eq!(
+            doc.to_string(),
+            input.replace("/var/log//cluster.log", "/srv/new//cluster.log")
+        );
+    }
+}
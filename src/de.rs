@@ -0,0 +1,398 @@
+//! Deserialization of a parsed config into any type implementing
+//! `serde::Deserialize`.
+//!
+//! Sections map to structs or maps and options to scalars. Keys which occur
+//! more than once, such as several `interface` or `node` sections, map to a
+//! `Vec`. Numbers are parsed from their text and booleans accept corosync's
+//! `yes`/`no`/`on`/`off`. Errors point at the offending option.
+
+use super::config::ConfigBlock;
+use super::error::{Error, ErrorType, Result};
+use super::model::parse_bool;
+use super::span::Span;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+
+/// Deserializes a `T` from a parsed config
+pub fn from_block<'de, T: de::Deserialize<'de>>(block: &'de ConfigBlock) -> Result<T> {
+    T::deserialize(SectionDeserializer { block })
+}
+
+/// Parses `input` and deserializes a `T` from it
+pub fn from_str<T: de::DeserializeOwned>(input: &str) -> Result<T> {
+    from_block(&super::parse(input)?)
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::new(0, 0, ErrorType::Custom(msg.to_string()), None)
+    }
+}
+
+/// Attaches the position of `span` to errors which have none yet
+fn locate<T>(result: Result<T>, span: Option<Span>) -> Result<T> {
+    match span {
+        Some(span) => result.map_err(|err| err.located(span.line, span.col)),
+        None => result,
+    }
+}
+
+/// Deserializes the inner blocks of a section as a map from names to values
+struct SectionDeserializer<'de> {
+    block: &'de ConfigBlock,
+}
+
+impl<'de> de::Deserializer<'de> for SectionDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut names: Vec<&'de str> = vec![];
+        for inner in self.block.inner() {
+            if !names.contains(&inner.name()) {
+                names.push(inner.name());
+            }
+        }
+        let result = visitor.visit_map(SectionAccess {
+            block: self.block,
+            names: names.into_iter(),
+            current: None,
+        });
+        locate(result, self.block.name_span())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+struct SectionAccess<'de> {
+    block: &'de ConfigBlock,
+    names: std::vec::IntoIter<&'de str>,
+    current: Option<&'de str>,
+}
+
+impl<'de> MapAccess<'de> for SectionAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.names.next() {
+            Some(name) => {
+                self.current = Some(name);
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let name = self.current.take().unwrap_or_default();
+        let blocks: Vec<&'de ConfigBlock> = self.block.matching(name).collect();
+        let span = blocks.last().and_then(|b| b.name_span());
+        locate(seed.deserialize(KeyDeserializer { blocks }), span)
+    }
+}
+
+/// Deserializes all inner blocks sharing one name. Sequences get every
+/// block, anything else the last one, as later options win in corosync
+struct KeyDeserializer<'de> {
+    blocks: Vec<&'de ConfigBlock>,
+}
+
+impl<'de> KeyDeserializer<'de> {
+    fn last(&self) -> ValueDeserializer<'de> {
+        ValueDeserializer {
+            block: self.blocks[self.blocks.len() - 1],
+        }
+    }
+}
+
+macro_rules! forward_to_last {
+    ($($method:ident)*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            self.last().$method(visitor)
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.blocks.len() > 1 {
+            self.deserialize_seq(visitor)
+        } else {
+            self.last().deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // A single option lists its parameters, as in `members: a b c`
+        if self.blocks.len() == 1 && !self.blocks[0].is_section() {
+            return self.last().deserialize_seq(visitor);
+        }
+        visitor.visit_seq(BlocksAccess {
+            blocks: self.blocks.into_iter(),
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.last().deserialize_option(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    forward_to_last! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16
+        deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_map
+        deserialize_identifier
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.last().deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.last().deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.last().deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        ignored_any
+    }
+}
+
+struct BlocksAccess<'de> {
+    blocks: std::vec::IntoIter<&'de ConfigBlock>,
+}
+
+impl<'de> SeqAccess<'de> for BlocksAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.blocks.next() {
+            Some(block) => {
+                let result = seed.deserialize(ValueDeserializer { block });
+                locate(result, block.name_span()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.blocks.len())
+    }
+}
+
+/// Deserializes a single block: sections as maps, options as scalars
+struct ValueDeserializer<'de> {
+    block: &'de ConfigBlock,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn text(&self) -> String {
+        (0..self.block.len())
+            .map(|i| self.block.get(i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn invalid<T>(&self, expected: &'static str) -> Result<T> {
+        let span = self
+            .block
+            .param_span(0)
+            .or_else(|| self.block.name_span())
+            .unwrap_or_default();
+        Err(Error::new(
+            span.line,
+            span.col,
+            ErrorType::InvalidValue(self.block.name().to_string(), self.text()),
+            Some(expected),
+        ))
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &'static str) -> Result<T> {
+        if self.block.is_section() {
+            return self.invalid(expected);
+        }
+        match self.text().parse() {
+            Ok(value) => Ok(value),
+            Err(_) => self.invalid(expected),
+        }
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(self.parse::<$ty>("a number")?)
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.block.is_section() {
+            return SectionDeserializer { block: self.block }.deserialize_any(visitor);
+        }
+        match self.block.len() {
+            0 => visitor.visit_unit(),
+            1 => visitor.visit_borrowed_str(self.block.get(0)),
+            _ => visitor.visit_string(self.text()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match parse_bool(&self.text()) {
+            Some(value) if !self.block.is_section() => visitor.visit_bool(value),
+            _ => self.invalid("yes, no, on or off"),
+        }
+    }
+
+    deserialize_number!(
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64
+    );
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // An option without a value, as in `provider:`, is treated as missing
+        if !self.block.is_section() && self.block.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.block.is_section() {
+            return self.invalid("an option");
+        }
+        let params: Vec<&'de str> = (0..self.block.len()).map(|i| self.block.get(i)).collect();
+        visitor.visit_seq(de::value::SeqDeserializer::new(params.into_iter()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.block.is_section() || self.block.len() != 1 {
+            return self.invalid("a single keyword");
+        }
+        visitor.visit_enum(self.block.get(0).into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Quorum {
+        provider: String,
+        two_node: bool,
+        expected_votes: Option<u32>,
+    }
+
+    #[test]
+    fn deserializes_scalars() {
+        let cfg = super::super::parse(
+            "provider: corosync_votequorum\ntwo_node: yes\nexpected_votes: 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            from_block::<Quorum>(&cfg),
+            Ok(Quorum {
+                provider: String::from("corosync_votequorum"),
+                two_node: true,
+                expected_votes: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_location_of_invalid_values() {
+        let cfg = super::super::parse("provider: votequorum\ntwo_node: maybe\n").unwrap();
+        let err = from_block::<Quorum>(&cfg).unwrap_err();
+        assert_eq!((err.line(), err.col()), (2, 11));
+        assert_eq!(err.expected(), Some("yes, no, on or off"));
+    }
+}
@@ -0,0 +1,102 @@
+use super::span::Span;
+use std::fmt;
+
+/// How serious a `Diagnostic` is
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a config which, unlike an `Error`, does not stop it
+/// from being used
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the kind of problem, e.g. `duplicate-key`
+    pub code: &'static str,
+    pub message: String,
+    /// Where the problem was found
+    pub span: Span,
+}
+
+/// A `;` which does not end an option, e.g. after a closing brace
+pub const STRAY_SEMICOLON: &str = "stray-semicolon";
+/// An option which is set more than once in the same section
+pub const DUPLICATE_KEY: &str = "duplicate-key";
+/// A section without any options or inner sections
+pub const EMPTY_SECTION: &str = "empty-section";
+/// Tokens which lenient parsing skipped over
+pub const SKIPPED_TOKENS: &str = "skipped-tokens";
+/// A top level section corosync does not know about
+pub const UNKNOWN_SECTION: &str = "unknown-section";
+/// A value corosync can not parse, e.g. a `token` which is not a number
+pub const INVALID_VALUE: &str = "invalid-value";
+/// A `bindnetaddr` which knet ignores in favour of the nodelist
+pub const BINDNETADDR_IGNORED: &str = "bindnetaddr-ignored";
+/// A transport which needs a nodelist without one
+pub const MISSING_NODELIST: &str = "missing-nodelist";
+/// A `crypto_cipher` without a `crypto_hash`
+pub const CRYPTO_MISMATCH: &str = "crypto-mismatch";
+/// A node without the `nodeid` knet needs
+pub const MISSING_NODEID: &str = "missing-nodeid";
+/// A `nodeid` of zero, which corosync reserves
+pub const INVALID_NODEID: &str = "invalid-nodeid";
+/// A `nodeid` used by more than one node
+pub const DUPLICATE_NODEID: &str = "duplicate-nodeid";
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            message,
+            span,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: String, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, code, message, span)
+    }
+
+    pub fn error(code: &'static str, message: String, span: Span) -> Diagnostic {
+        Diagnostic::new(Severity::Error, code, message, span)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {} at line {}, column {}",
+            self.severity, self.code, self.message, self.span.line, self.span.col
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn displays_severity_code_and_location() {
+        let diagnostic = Diagnostic::warning(
+            EMPTY_SECTION,
+            String::from("section `quorum` is empty"),
+            Span::new(10, 18, 3, 5),
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning[empty-section]: section `quorum` is empty at line 3, column 5"
+        );
+    }
+}
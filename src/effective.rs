@@ -0,0 +1,268 @@
+use super::cmap;
+use super::config::ConfigBlock;
+use super::model::parse_bool;
+use super::span::Span;
+use std::fmt;
+
+/// The corosync release whose defaults are filled in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum CorosyncVersion {
+    /// corosync 2.x, which defaults to the `udp` transport
+    V2,
+    /// corosync 3.x, which defaults to the `knet` transport
+    #[default]
+    V3,
+}
+
+/// Where an effective value comes from
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Origin {
+    /// Set in the config
+    Explicit,
+    /// Built into corosync
+    Default,
+    /// Computed by corosync from other values, e.g. `consensus` from `token`
+    Derived,
+}
+
+impl Origin {
+    /// Returns the lowercase name, e.g. `default`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Origin::Explicit => "explicit",
+            Origin::Default => "default",
+            Origin::Derived => "derived",
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A cmap key with the value corosync uses for it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EffectiveValue {
+    pub key: String,
+    pub value: String,
+    pub origin: Origin,
+    /// Where an explicit value was found, if it was parsed
+    pub span: Option<Span>,
+}
+
+/// A config with every omitted default filled in
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct EffectiveConfig {
+    values: Vec<EffectiveValue>,
+}
+
+impl EffectiveConfig {
+    /// Returns the explicit values in file order, followed by the defaults and
+    /// derived values
+    pub fn values(&self) -> &[EffectiveValue] {
+        &self.values
+    }
+
+    /// Returns the entry for the cmap key `key`, e.g. `totem.consensus`
+    pub fn get(&self, key: &str) -> Option<&EffectiveValue> {
+        self.values.iter().find(|v| v.key == key)
+    }
+
+    /// Returns the value for the cmap key `key`
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.get(key).map(|v| v.value.as_str())
+    }
+
+    fn number(&self, key: &str) -> Option<u32> {
+        self.value(key).and_then(|v| v.parse().ok())
+    }
+
+    /// Adds `key` unless the config sets it
+    fn fill(&mut self, key: &str, value: String, origin: Origin) {
+        if self.get(key).is_none() {
+            self.values.push(EffectiveValue {
+                key: key.to_string(),
+                value,
+                origin,
+                span: None,
+            });
+        }
+    }
+}
+
+/// Defaults of `totem` shared by corosync 2.x and 3.x
+const TOTEM_DEFAULTS: &[(&str, &str)] = &[
+    ("version", "2"),
+    ("token_coefficient", "650"),
+    ("token_retransmits_before_loss_const", "4"),
+    ("join", "50"),
+    ("send_join", "0"),
+    ("merge", "200"),
+    ("downcheck", "1000"),
+    ("fail_recv_const", "2500"),
+    ("seqno_unchanged_const", "30"),
+    ("max_messages", "17"),
+    ("window_size", "50"),
+    ("max_network_delay", "50"),
+    ("miss_count_const", "5"),
+    ("heartbeat_failures_allowed", "0"),
+];
+
+/// Defaults of `logging` shared by corosync 2.x and 3.x
+const LOGGING_DEFAULTS: &[(&str, &str)] = &[
+    ("to_stderr", "yes"),
+    ("to_logfile", "no"),
+    ("to_syslog", "yes"),
+    ("debug", "off"),
+    ("fileline", "off"),
+    ("function_name", "off"),
+];
+
+/// Resolves `block`, the root of a parsed config, to the values `version` of
+/// corosync runs with. Values are keyed like `ConfigBlock::to_cmap`, and if
+/// the config sets a key more than once the last value counts.
+///
+/// `consensus` is derived as 1.2 times the token timeout, which grows by
+/// `token_coefficient` for every node beyond the second, and
+/// `token_retransmit` from the token timeout and
+/// `token_retransmits_before_loss_const`. Derived values are left out if the
+/// values they depend on are not numbers
+pub fn effective(block: &ConfigBlock, version: CorosyncVersion) -> EffectiveConfig {
+    let mut config = EffectiveConfig::default();
+    for (key, value, option) in cmap::entries(block) {
+        let span = option.span();
+        match config.values.iter_mut().find(|v| v.key == key) {
+            Some(existing) => {
+                existing.value = value;
+                existing.span = span;
+            }
+            None => config.values.push(EffectiveValue {
+                key,
+                value,
+                origin: Origin::Explicit,
+                span,
+            }),
+        }
+    }
+
+    let transport = match version {
+        CorosyncVersion::V2 => "udp",
+        CorosyncVersion::V3 => "knet",
+    };
+    config.fill("totem.transport", transport.to_string(), Origin::Default);
+    let ip_version = match config.value("totem.transport") {
+        Some("knet") => "ipv6-4",
+        _ => "ipv4",
+    };
+    config.fill("totem.ip_version", ip_version.to_string(), Origin::Default);
+
+    // corosync 2.x turns on aes256 and sha1 for `secauth: on`
+    let secauth = config
+        .value("totem.secauth")
+        .and_then(parse_bool)
+        .unwrap_or(false);
+    let (cipher, hash, origin) = match version {
+        CorosyncVersion::V2 if secauth => ("aes256", "sha1", Origin::Derived),
+        _ => ("none", "none", Origin::Default),
+    };
+    config.fill("totem.crypto_cipher", cipher.to_string(), origin);
+    config.fill("totem.crypto_hash", hash.to_string(), origin);
+
+    let token = match version {
+        CorosyncVersion::V2 => "1000",
+        CorosyncVersion::V3 => "3000",
+    };
+    config.fill("totem.token", token.to_string(), Origin::Default);
+    for (name, value) in TOTEM_DEFAULTS {
+        config.fill(
+            &format!("totem.{}", name),
+            value.to_string(),
+            Origin::Default,
+        );
+    }
+
+    let nodes = node_count(&config);
+    let token = match (
+        config.number("totem.token"),
+        config.number("totem.token_coefficient"),
+    ) {
+        (Some(token), Some(coefficient)) if nodes > 2 => coefficient
+            .checked_mul(nodes - 2)
+            .and_then(|c| token.checked_add(c)),
+        (token, _) => token,
+    };
+    if let Some(token) = token {
+        let consensus = (u64::from(token) * 12 / 10).to_string();
+        config.fill("totem.consensus", consensus, Origin::Derived);
+        if let Some(retransmits) = config.number("totem.token_retransmits_before_loss_const") {
+            let retransmit = (f64::from(token) / (f64::from(retransmits) + 0.2)) as u32;
+            config.fill(
+                "totem.token_retransmit",
+                retransmit.to_string(),
+                Origin::Derived,
+            );
+        }
+    }
+
+    for (name, value) in LOGGING_DEFAULTS {
+        config.fill(
+            &format!("logging.{}", name),
+            value.to_string(),
+            Origin::Default,
+        );
+    }
+    config
+}
+
+/// Counts the nodes of the nodelist by their cmap index
+fn node_count(config: &EffectiveConfig) -> u32 {
+    let mut indices: Vec<&str> = vec![];
+    for value in &config.values {
+        let index = value
+            .key
+            .strip_prefix("nodelist.node.")
+            .and_then(|rest| rest.split('.').next());
+        if let Some(index) = index {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+    }
+    indices.len() as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn option(name: &str, value: &str) -> ConfigBlock {
+        ConfigBlock::option(String::from(name), vec![String::from(value)])
+    }
+
+    #[test]
+    fn derives_consensus_from_the_token_of_large_clusters() {
+        let node = |id: &str| {
+            ConfigBlock::section(String::from("node"), vec![], vec![option("nodeid", id)])
+        };
+        let block = ConfigBlock::new(
+            String::new(),
+            vec![],
+            vec![
+                ConfigBlock::section(String::from("totem"), vec![], vec![option("token", "1000")]),
+                ConfigBlock::section(
+                    String::from("nodelist"),
+                    vec![],
+                    vec![node("1"), node("2"), node("3"), node("4")],
+                ),
+            ],
+        );
+        let config = effective(&block, CorosyncVersion::V3);
+        assert_eq!(config.get("totem.token").unwrap().origin, Origin::Explicit);
+        let consensus = config.get("totem.consensus").unwrap();
+        assert_eq!(consensus.value, "2760");
+        assert_eq!(consensus.origin, Origin::Derived);
+        assert_eq!(config.value("totem.token_retransmit"), Some("547"));
+    }
+}
@@ -1,4 +1,8 @@
-use super::lexer::Token;
+use super::lexer::{Token, TokenType};
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::result;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -6,30 +10,96 @@ pub type Result<T> = result::Result<T, Error>;
 #[derive(Debug, PartialEq, Eq)]
 pub enum ErrorType {
     UnexpectedEOF,
-    Unexpected(Token),
+    Unexpected(Token<'static>),
     MissingParameter(String),
+    InvalidUtf8,
+    Io(io::ErrorKind, String),
+    InvalidQuery(String),
+    InvalidDump(String),
+    InvalidValue(String, String),
+    DuplicateKey(String),
+    InvalidEscape(String),
+    TooDeeplyNested,
+    Custom(String),
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorType::UnexpectedEOF => write!(f, "unexpected end of file"),
+            ErrorType::Unexpected(token) => write!(f, "unexpected {}", token.token_type),
+            ErrorType::MissingParameter(name) => write!(f, "missing parameter `{}`", name),
+            ErrorType::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            ErrorType::Io(_, message) => write!(f, "I/O error: {}", message),
+            ErrorType::InvalidQuery(message) => write!(f, "invalid query: {}", message),
+            ErrorType::InvalidDump(message) => write!(f, "invalid cmap dump: {}", message),
+            ErrorType::InvalidValue(key, value) => {
+                write!(f, "invalid value {:?} for `{}`", value, key)
+            }
+            ErrorType::DuplicateKey(key) => write!(f, "duplicate key `{}`", key),
+            ErrorType::InvalidEscape(sequence) => {
+                write!(f, "invalid escape sequence `{}`", sequence)
+            }
+            ErrorType::TooDeeplyNested => write!(f, "sections are nested too deeply"),
+            ErrorType::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl fmt::Display for TokenType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenType::StringLiteral(s) => write!(f, "string {:?}", s),
+            TokenType::RawLiteral(s) => write!(f, "`{}`", s),
+            TokenType::OpenBrace => write!(f, "`{{`"),
+            TokenType::CloseBrace => write!(f, "`}}`"),
+            TokenType::OpenParen => write!(f, "`(`"),
+            TokenType::CloseParen => write!(f, "`)`"),
+            TokenType::Semicolon => write!(f, "`;`"),
+            TokenType::Colon => write!(f, "`:`"),
+            TokenType::LineEnd => write!(f, "end of line"),
+        }
+    }
 }
 
 pub trait CodePosition {
-    fn location(&self) -> (u32, u16);
+    fn location(&self) -> (u32, u32);
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Error {
     error_type: ErrorType,
     line: u32,
-    col: u16,
+    col: u32,
     expected: Option<&'static str>,
+    path: Option<PathBuf>,
 }
 
 impl Error {
-    pub fn new(line: u32, col: u16, etype: ErrorType, expected: Option<&'static str>) -> Error {
+    pub fn new(line: u32, col: u32, etype: ErrorType, expected: Option<&'static str>) -> Error {
         Error {
             error_type: etype,
-            line: line,
-            col: col,
-            expected: expected,
+            line,
+            col,
+            expected,
+            path: None,
+        }
+    }
+
+    /// Sets the position of an error which has none yet
+    #[cfg(feature = "serde")]
+    pub(crate) fn located(mut self, line: u32, col: u32) -> Error {
+        if self.line == 0 {
+            self.line = line;
+            self.col = col;
         }
+        self
+    }
+
+    /// Attaches the path of the file that was being read
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Error {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
     }
 
     pub fn from_state<T>(pos: &T, etype: ErrorType, expected: Option<&'static str>) -> Error
@@ -39,4 +109,105 @@ impl Error {
         let p = pos.location();
         Error::new(p.0, p.1, etype, expected)
     }
+
+    /// Returns what went wrong
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error_type
+    }
+
+    /// Returns the line the error occurred on, starting at 1
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the column the error occurred on, starting at 1
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    /// Returns a hint on what the parser expected instead, if any
+    pub fn expected(&self) -> Option<&'static str> {
+        self.expected
+    }
+
+    /// Returns the path of the file that was being read, if known
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns true if the error was caused by reading the input rather than
+    /// by its contents
+    pub fn is_io(&self) -> bool {
+        matches!(self.error_type, ErrorType::Io(..))
+    }
+
+    /// Renders the error together with the offending line of `source`, which
+    /// must be the text that was parsed, and a caret pointing at the column
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("error: {}\n", self.error_type);
+        let line_text = match self.line.checked_sub(1) {
+            Some(index) => source.lines().nth(index as usize),
+            None => None,
+        };
+        let location = match self.path {
+            Some(ref path) => format!("{}:{}:{}", path.display(), self.line, self.col),
+            None => format!("{}:{}", self.line, self.col),
+        };
+        let line_text = match line_text {
+            Some(text) => text,
+            None => {
+                out.push_str(&format!(" --> {}\n", location));
+                if let Some(expected) = self.expected {
+                    out.push_str(&format!("  = expected {}\n", expected));
+                }
+                return out;
+            }
+        };
+
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let caret_offset: String = line_text
+            .chars()
+            .take(self.col.saturating_sub(1) as usize)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        out.push_str(&format!("{}--> {}\n", gutter, location));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", number, line_text));
+        out.push_str(&format!("{} | {}^", gutter, caret_offset));
+        if let Some(expected) = self.expected {
+            out.push_str(&format!(" expected {}", expected));
+        }
+        out.push('\n');
+        out
+    }
 }
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::new(0, 0, ErrorType::Io(err.kind(), err.to_string()), None)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref path) = self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        if self.is_io() {
+            return write!(f, "{}", self.error_type);
+        }
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.error_type, self.line, self.col
+        )?;
+        if let Some(expected) = self.expected {
+            write!(f, ", expected {}", expected)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
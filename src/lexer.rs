@@ -1,16 +1,23 @@
 use super::error::{CodePosition, Error, ErrorType, Result};
+use super::parser::{Comments, ParseOptions};
+use super::span::Span;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::str::Chars;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum LexerMode {
     None,
     String,
     Raw,
+    /// The rest of the line after the colon of an option
+    Line,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub enum TokenType {
-    StringLiteral(String),
-    RawLiteral(String),
+pub enum TokenType<'a> {
+    StringLiteral(Cow<'a, str>),
+    RawLiteral(Cow<'a, str>),
     OpenBrace,
     CloseBrace,
     OpenParen,
@@ -21,171 +28,463 @@ pub enum TokenType {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Token {
-    pub token_type: TokenType,
-    pub line: u32,
-    pub col: u16,
+pub struct Token<'a> {
+    pub token_type: TokenType<'a>,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(line: u32, col: u16, ty: TokenType) -> Token {
+impl<'a> Token<'a> {
+    pub fn new(span: Span, ty: TokenType<'a>) -> Token<'a> {
         Token {
-            line: line,
-            col: col,
+            span,
             token_type: ty,
         }
     }
+
+    /// Copies borrowed text, so the token no longer borrows from the input
+    pub fn into_owned(self) -> Token<'static> {
+        let token_type = match self.token_type {
+            TokenType::StringLiteral(s) => TokenType::StringLiteral(Cow::Owned(s.into_owned())),
+            TokenType::RawLiteral(s) => TokenType::RawLiteral(Cow::Owned(s.into_owned())),
+            TokenType::OpenBrace => TokenType::OpenBrace,
+            TokenType::CloseBrace => TokenType::CloseBrace,
+            TokenType::OpenParen => TokenType::OpenParen,
+            TokenType::CloseParen => TokenType::CloseParen,
+            TokenType::Semicolon => TokenType::Semicolon,
+            TokenType::Colon => TokenType::Colon,
+            TokenType::LineEnd => TokenType::LineEnd,
+        };
+        Token::new(self.span, token_type)
+    }
+}
+
+impl CodePosition for Token<'_> {
+    fn location(&self) -> (u32, u32) {
+        (self.span.line, self.span.col)
+    }
 }
 
-struct LexerState {
+struct LexerState<'a> {
     line: u32,
-    col: u16,
-    input: Box<dyn Iterator<Item = char>>,
+    col: u32,
+    offset: usize,
+    /// Span of the character last returned by `next`
+    char_span: Span,
+    /// End of the character returned by `next` before the current one
+    prev_end: usize,
+    /// Span of the first character of the token being built
+    token_start: Span,
+    source: &'a str,
+    input: Chars<'a>,
     mode: LexerMode,
-    escaped: bool,
-    tmp: String,
-    tokens: Vec<Token>,
+    /// Span of the backslash while reading an escape sequence
+    escaped: Option<Span>,
+    /// Byte range of the input holding the text of the literal being built,
+    /// as long as it can be borrowed
+    content_start: usize,
+    content_end: usize,
+    /// Text of the literal being built, once it differs from the input
+    owned: Option<String>,
+    /// Tokens which are complete but have not been returned yet
+    pending: VecDeque<Token<'a>>,
+    /// Whether the last token would be terminated by a line end
+    needs_line_end: bool,
+    /// Whether the colon separating the current option from its value has
+    /// been read. Later colons are part of the value, e.g. in `fe80::1`
+    in_value: bool,
     force_next: Option<char>,
+    comments: Comments,
+    /// Whether parentheses are read as part of values
+    parentheses: bool,
+    /// Whether only whitespace has been read on the current line
+    at_line_start: bool,
+    /// Whether the value of an option is the rest of its line
+    line_values: bool,
+    /// End of the last character of a line value which is not whitespace
+    value_end: usize,
 }
 
-impl CodePosition for LexerState {
-    fn location(&self) -> (u32, u16) {
+impl CodePosition for LexerState<'_> {
+    fn location(&self) -> (u32, u32) {
         (self.line, self.col)
     }
 }
 
-fn end_token(state: &mut LexerState) {
-    if state.mode != LexerMode::None {
-        let t = match state.mode {
-            LexerMode::None => unreachable!("Invalid mode when generating token"),
-            LexerMode::String => Token::new(
-                state.line,
-                state.col,
-                TokenType::StringLiteral(state.tmp.clone()),
-            ),
-            LexerMode::Raw => Token::new(
-                state.line,
-                state.col,
-                TokenType::RawLiteral(state.tmp.clone()),
-            ),
-        };
-        state.mode = LexerMode::None;
-        state.tokens.push(t);
+/// Splits a config file into tokens lazily. Literals borrow their text from
+/// the input unless it contains escape sequences or comments. Iteration ends
+/// after the first error
+pub struct Lexer<'a> {
+    state: LexerState<'a>,
+    error: Option<Error>,
+    /// Set once the input is exhausted or an error was found
+    done: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Lexer<'a> {
+        Lexer::with_options(input, &ParseOptions::default())
+    }
+
+    /// Creates a lexer with the comment styles and handling of parentheses
+    /// taken from `options`
+    pub fn with_options(input: &'a str, options: &ParseOptions) -> Lexer<'a> {
+        Lexer {
+            state: LexerState {
+                line: 1,
+                col: 0,
+                offset: 0,
+                char_span: Span::default(),
+                prev_end: 0,
+                token_start: Span::default(),
+                source: input,
+                input: input.chars(),
+                mode: LexerMode::None,
+                escaped: None,
+                content_start: 0,
+                content_end: 0,
+                owned: None,
+                pending: VecDeque::new(),
+                needs_line_end: false,
+                in_value: false,
+                force_next: None,
+                comments: options.comments,
+                parentheses: options.parentheses,
+                at_line_start: true,
+                line_values: options.line_values,
+                value_end: 0,
+            },
+            error: None,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Result<Token<'a>>> {
+        loop {
+            if let Some(t) = self.state.pending.pop_front() {
+                return Some(Ok(t));
+            }
+            if let Some(err) = self.error.take() {
+                return Some(Err(err));
+            }
+            if self.done {
+                return None;
+            }
+            match step(&mut self.state) {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(err) => {
+                    self.error = Some(err);
+                    self.done = true;
+                }
+            }
+        }
     }
 }
 
+fn emit<'a>(state: &mut LexerState<'a>, t: Token<'a>) {
+    let ends_option = matches!(
+        t.token_type,
+        TokenType::LineEnd | TokenType::OpenBrace | TokenType::CloseBrace | TokenType::Semicolon
+    );
+    state.needs_line_end = !ends_option;
+    if ends_option {
+        state.in_value = false;
+    } else if t.token_type == TokenType::Colon {
+        state.in_value = true;
+    }
+    state.pending.push_back(t);
+}
+
+/// Returns the text of the literal being built
+fn take_text<'a>(state: &mut LexerState<'a>) -> Cow<'a, str> {
+    match state.owned.take() {
+        Some(text) => Cow::Owned(text),
+        None => Cow::Borrowed(
+            state
+                .source
+                .get(state.content_start..state.content_end)
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+/// Adds `c` to the literal being built. As long as the literal is a
+/// contiguous part of the input, its text is only borrowed
+fn push_char(state: &mut LexerState, c: char) {
+    let span = state.char_span;
+    if let Some(ref mut text) = state.owned {
+        text.push(c);
+        return;
+    }
+    let verbatim = span.start == state.content_end
+        && state
+            .source
+            .get(span.start..span.end)
+            .and_then(|s| s.chars().next())
+            == Some(c);
+    if verbatim {
+        state.content_end = span.end;
+    } else {
+        let mut text = state
+            .source
+            .get(state.content_start..state.content_end)
+            .unwrap_or_default()
+            .to_string();
+        text.push(c);
+        state.owned = Some(text);
+    }
+}
+
+/// Adds `c` to a line value. Whitespace is kept as written instead of as the
+/// space the preprocessor turns it into
+fn push_line_char(state: &mut LexerState, c: char) {
+    let span = state.char_span;
+    if c == ' ' {
+        let written = state.source.get(span.start..span.end);
+        push_char(state, written.and_then(|s| s.chars().next()).unwrap_or(c));
+    } else {
+        push_char(state, c);
+        state.value_end = span.end;
+    }
+}
+
+fn end_token(state: &mut LexerState) {
+    let t = match state.mode {
+        LexerMode::None => return,
+        // The closing quote is the current character and part of the token
+        LexerMode::String => Token::new(
+            state.token_start.to(state.char_span),
+            TokenType::StringLiteral(take_text(state)),
+        ),
+        // Raw literals end on the character before the delimiter
+        LexerMode::Raw => Token::new(
+            Span {
+                end: state.prev_end,
+                ..state.token_start
+            },
+            TokenType::RawLiteral(take_text(state)),
+        ),
+        // Trailing whitespace is not part of a line value
+        LexerMode::Line => {
+            let text = match take_text(state) {
+                Cow::Borrowed(s) => Cow::Borrowed(s.trim_end()),
+                Cow::Owned(mut s) => {
+                    s.truncate(s.trim_end().len());
+                    Cow::Owned(s)
+                }
+            };
+            Token::new(
+                Span {
+                    end: state.value_end,
+                    ..state.token_start
+                },
+                TokenType::RawLiteral(text),
+            )
+        }
+    };
+    state.mode = LexerMode::None;
+    emit(state, t);
+}
+
 fn start_token(state: &mut LexerState, mode: LexerMode) {
     if state.mode != LexerMode::None {
         end_token(state);
     }
-    state.tmp = String::new();
+    state.owned = None;
+    state.token_start = state.char_span;
+    // The text of a string starts after the opening quote
+    state.content_start = match mode {
+        LexerMode::String => state.char_span.end,
+        _ => state.char_span.start,
+    };
+    state.content_end = state.content_start;
     state.mode = mode;
 }
 
-fn append_token(state: &mut LexerState, t: TokenType) {
+fn append_token<'a>(state: &mut LexerState<'a>, t: TokenType<'a>) {
     end_token(state);
-    state.tokens.push(Token::new(state.line, state.col, t));
+    let span = state.char_span;
+    emit(state, Token::new(span, t));
 }
 
-pub fn run(input: Box<dyn Iterator<Item = char>>) -> Result<Vec<Token>> {
-    let mut state = LexerState {
-        line: 1,
-        col: 0,
-        input: input,
-        mode: LexerMode::None,
-        escaped: false,
-        tmp: String::new(),
-        tokens: vec![],
-        force_next: None,
-    };
-    loop {
-        let c = { next(&mut state) };
-        let mode = state.mode.clone();
-        let esc = state.escaped;
-        match (c, mode, esc) {
-            (Some('"'), LexerMode::String, false) => {
-                end_token(&mut state);
-            }
-            (Some('"'), LexerMode::None, false) => {
-                start_token(&mut state, LexerMode::String);
-            }
-            (Some('\\'), LexerMode::String, false) => {
-                state.escaped = true;
-            }
-            (Some('\\'), LexerMode::String, true) => {
-                state.tmp.push('\\');
-                state.escaped = false;
-            }
-            (Some('n'), LexerMode::String, true) => {
-                state.tmp.push('\n');
-                state.escaped = false;
-            }
-            (Some(x), LexerMode::String, false) => {
-                state.tmp.push(x);
-            }
-            (None, LexerMode::String, _) => return fail(&state, ErrorType::UnexpectedEOF),
-            (Some(' '), LexerMode::None, false) => {}
-            (Some(' '), LexerMode::Raw, false) => {
-                end_token(&mut state);
-            }
-            (Some('('), LexerMode::Raw, false) => {
-                append_token(&mut state, TokenType::OpenParen);
-            }
-            (Some(')'), LexerMode::Raw, false) => {
-                append_token(&mut state, TokenType::CloseParen);
-            }
-            (Some('{'), LexerMode::Raw, false) => {
-                append_token(&mut state, TokenType::OpenBrace);
-            }
-            (Some('}'), LexerMode::Raw, false) => {
-                append_token(&mut state, TokenType::CloseBrace);
-            }
-            (Some(':'), LexerMode::Raw, false) => {
-                append_token(&mut state, TokenType::Colon);
-            }
-            (Some('('), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::OpenParen);
-            }
-            (Some(')'), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::CloseParen);
-            }
-            (Some('{'), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::OpenBrace);
-            }
-            (Some('}'), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::CloseBrace);
-            }
-            (Some(';'), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::Semicolon);
-            }
-            (Some(':'), LexerMode::None, false) => {
-                append_token(&mut state, TokenType::Colon);
-            }
-            (Some('\n'), LexerMode::None, false) => {}
-            (Some('\n'), LexerMode::Raw, false) => append_token(&mut state, TokenType::LineEnd),
-            (Some(x), LexerMode::None, false) => {
-                start_token(&mut state, LexerMode::Raw);
-                state.tmp.push(x);
-            }
-            (Some(x), LexerMode::Raw, false) => {
-                state.tmp.push(x);
-            }
-            (None, LexerMode::Raw, false) => {
-                end_token(&mut state);
-                break;
+pub fn run(input: &str) -> Result<Vec<Token<'_>>> {
+    Lexer::new(input).collect()
+}
+
+/// Like `run`, but with the comment styles and handling of parentheses taken
+/// from `options`
+pub fn run_with<'a>(input: &'a str, options: &ParseOptions) -> Result<Vec<Token<'a>>> {
+    Lexer::with_options(input, options).collect()
+}
+
+/// Reads the next character and the tokens it completes. Returns false at the
+/// end of the input
+fn step(state: &mut LexerState) -> Result<bool> {
+    let c = next(state);
+    let mode = state.mode;
+    let esc = state.escaped.is_some();
+    match (c, mode, esc) {
+        // Quotes and all other syntax are part of a line value
+        (Some(x), LexerMode::None, _)
+            if state.line_values && state.in_value && x != ' ' && x != '\n' =>
+        {
+            start_token(state, LexerMode::Line);
+            push_line_char(state, x);
+        }
+        (Some('\n'), LexerMode::Line, _) => append_token(state, TokenType::LineEnd),
+        (Some(x), LexerMode::Line, _) => push_line_char(state, x),
+        (None, LexerMode::Line, _) => {
+            end_token(state);
+            return Ok(false);
+        }
+        (Some('"'), LexerMode::String, false) => {
+            end_token(state);
+        }
+        (Some('"'), LexerMode::None, _) => {
+            start_token(state, LexerMode::String);
+        }
+        (Some('\\'), LexerMode::String, false) => {
+            state.escaped = Some(state.char_span);
+        }
+        (Some(x), LexerMode::String, true) => {
+            let c = read_escape(state, x)?;
+            push_char(state, c);
+            state.escaped = None;
+        }
+        (Some(x), LexerMode::String, false) => {
+            push_char(state, x);
+        }
+        (None, LexerMode::String, _) => return fail(state, ErrorType::UnexpectedEOF),
+        (Some(' '), LexerMode::None, _) => {}
+        (Some(' '), LexerMode::Raw, _) => {
+            end_token(state);
+        }
+        (Some('('), LexerMode::Raw, _) if !state.parentheses => {
+            append_token(state, TokenType::OpenParen);
+        }
+        (Some(')'), LexerMode::Raw, _) if !state.parentheses => {
+            append_token(state, TokenType::CloseParen);
+        }
+        (Some('{'), LexerMode::Raw, _) => {
+            append_token(state, TokenType::OpenBrace);
+        }
+        (Some('}'), LexerMode::Raw, _) => {
+            append_token(state, TokenType::CloseBrace);
+        }
+        (Some(':'), LexerMode::Raw, _) if !state.in_value => {
+            append_token(state, TokenType::Colon);
+        }
+        (Some('('), LexerMode::None, _) if !state.parentheses => {
+            append_token(state, TokenType::OpenParen);
+        }
+        (Some(')'), LexerMode::None, _) if !state.parentheses => {
+            append_token(state, TokenType::CloseParen);
+        }
+        (Some('{'), LexerMode::None, _) => {
+            append_token(state, TokenType::OpenBrace);
+        }
+        (Some('}'), LexerMode::None, _) => {
+            append_token(state, TokenType::CloseBrace);
+        }
+        (Some(';'), LexerMode::None, _) => {
+            append_token(state, TokenType::Semicolon);
+        }
+        (Some(':'), LexerMode::None, _) if !state.in_value => {
+            append_token(state, TokenType::Colon);
+        }
+        (Some('\n'), LexerMode::None, _) => {
+            // Only a line end which terminates something is of interest,
+            // e.g. after a quoted value, not after braces or empty lines
+            if state.needs_line_end {
+                append_token(state, TokenType::LineEnd);
             }
-            (None, LexerMode::None, false) => {
-                break;
+        }
+        (Some('\n'), LexerMode::Raw, _) => append_token(state, TokenType::LineEnd),
+        (Some(x), LexerMode::None, _) => {
+            start_token(state, LexerMode::Raw);
+            push_char(state, x);
+        }
+        (Some(x), LexerMode::Raw, _) => {
+            push_char(state, x);
+        }
+        (None, LexerMode::Raw, _) => {
+            end_token(state);
+            return Ok(false);
+        }
+        (None, LexerMode::None, _) => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Resolves the escape sequence starting with `c` after a backslash, reading
+/// more characters where the sequence needs them
+fn read_escape(state: &mut LexerState, c: char) -> Result<char> {
+    let resolved = match c {
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        'x' => {
+            let mut digits = String::new();
+            for _ in 0..2 {
+                match next(state) {
+                    Some(d) => digits.push(d),
+                    None => return fail(state, ErrorType::UnexpectedEOF),
+                }
             }
-            (c, mode, esc) => {
-                unreachable!(
-                    "Invalid Parser State Reached: {:?}, {:?}, {:?}",
-                    c, mode, esc
-                );
+            // Only ASCII, as a single byte above it is no valid UTF-8
+            parse_hex(&digits)
+                .filter(|n| *n < 0x80)
+                .and_then(char::from_u32)
+        }
+        'u' => {
+            if next(state) != Some('{') {
+                return invalid_escape(state);
+            }
+            let mut digits = String::new();
+            loop {
+                match next(state) {
+                    Some('}') => break,
+                    Some(d) if digits.len() < 6 => digits.push(d),
+                    Some(_) => return invalid_escape(state),
+                    None => return fail(state, ErrorType::UnexpectedEOF),
+                }
             }
+            parse_hex(&digits).and_then(char::from_u32)
         }
+        _ => None,
+    };
+    match resolved {
+        Some(c) => Ok(c),
+        None => invalid_escape(state),
+    }
+}
+
+fn parse_hex(digits: &str) -> Option<u32> {
+    if digits.is_empty() || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+        return None;
     }
-    Ok(state.tokens)
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Fails with the escape sequence read so far, located at its backslash
+fn invalid_escape<T>(state: &LexerState) -> Result<T> {
+    let start = state.escaped.unwrap_or(state.char_span);
+    let sequence = state
+        .source
+        .get(start.start..state.char_span.end)
+        .unwrap_or_default();
+    Err(Error::new(
+        start.line,
+        start.col,
+        ErrorType::InvalidEscape(sequence.to_string()),
+        Some("\\\\, \\\", \\n, \\t, \\r, \\xNN or \\u{...}"),
+    ))
 }
 
 fn fail<T>(state: &LexerState, error_type: ErrorType) -> Result<T> {
@@ -200,13 +499,23 @@ enum PreProcessorState {
 }
 
 fn next_char(state: &mut LexerState) -> Option<char> {
-    match state.force_next {
+    let c = match state.force_next {
         Some(c) => {
             state.force_next = None;
             Some(c)
         }
         None => state.input.next(),
+    };
+    if let Some(c) = c {
+        state.offset += c.len_utf8();
+        if c == '\n' {
+            state.line += 1;
+            state.col = 0;
+        } else {
+            state.col += 1;
+        }
     }
+    c
 }
 
 fn lookahead(state: &mut LexerState) -> Option<char> {
@@ -221,90 +530,95 @@ fn lookahead(state: &mut LexerState) -> Option<char> {
 }
 
 fn next(state: &mut LexerState) -> Option<char> {
-    let mut line = state.line;
-    let mut column = state.col;
     let mut result: Option<char> = None;
     let mut pre_processor_state = PreProcessorState::Default;
+    state.prev_end = state.char_span.end;
     loop {
+        let start = Span::new(state.offset, state.offset, state.line, state.col + 1);
         let character = match next_char(state) {
             Some(c) => c,
             None => break,
         };
+        let span = Span {
+            end: state.offset,
+            ..start
+        };
+        // Quoted strings are taken verbatim, comment markers included
+        if state.mode == LexerMode::String {
+            result = Some(character);
+            state.char_span = span;
+            break;
+        }
         match (character, pre_processor_state) {
             ('\n', PreProcessorState::Default) => {
-                line += 1;
-                column = 0;
                 result = Some('\n');
+                state.char_span = span;
                 break;
             }
             ('\r', PreProcessorState::Default) => {}
             ('/', PreProcessorState::Default) => {
-                column += 1;
                 let n = lookahead(state);
                 match n {
-                    Some('/') => pre_processor_state = PreProcessorState::LineComment,
-                    Some('*') => pre_processor_state = PreProcessorState::MultiComment(1),
+                    Some('/') if state.comments.double_slash => {
+                        pre_processor_state = PreProcessorState::LineComment
+                    }
+                    Some('*') if state.comments.block => {
+                        pre_processor_state = PreProcessorState::MultiComment(1)
+                    }
                     _ => {
                         result = Some(character);
+                        state.char_span = span;
                         break;
                     }
                 }
             }
-            ('#', PreProcessorState::Default) => {
+            ('#', PreProcessorState::Default)
+                if state.comments.hash
+                    && (state.at_line_start || !state.comments.hash_only_at_line_start) =>
+            {
                 pre_processor_state = PreProcessorState::LineComment;
-                column += 1;
             }
             (c, PreProcessorState::Default) if c.is_whitespace() => {
-                column += 1;
                 result = Some(' ');
+                state.char_span = span;
                 break;
             }
             (_, PreProcessorState::Default) => {
                 result = Some(character);
-                column += 1;
+                state.char_span = span;
                 break;
             }
 
             ('\n', PreProcessorState::LineComment) => {
-                line += 1;
-                column = 0;
-                result = Some(' ');
+                result = Some('\n');
+                state.char_span = span;
                 break;
             }
-            (_, PreProcessorState::LineComment) => {
-                column += 1;
-            }
+            (_, PreProcessorState::LineComment) => {}
 
-            ('\n', PreProcessorState::MultiComment(_)) => {
-                line += 1;
-                column = 0;
-            }
             ('*', PreProcessorState::MultiComment(level)) => {
-                match lookahead(state) {
-                    Some('/') => {
-                        if level <= 1 {
-                            next(state).unwrap(); // pop the next char
-                            pre_processor_state = PreProcessorState::Default
-                        } else {
-                            pre_processor_state = PreProcessorState::MultiComment(level - 1)
-                        }
+                if let Some('/') = lookahead(state) {
+                    if level <= 1 {
+                        next_char(state); // pop the next char
+                        pre_processor_state = PreProcessorState::Default
+                    } else {
+                        pre_processor_state = PreProcessorState::MultiComment(level - 1)
                     }
-                    _ => {}
                 }
             }
-            ('/', PreProcessorState::MultiComment(level)) => match lookahead(state) {
-                Some('*') => {
-                    pre_processor_state = PreProcessorState::MultiComment(level + 1);
+            ('/', PreProcessorState::MultiComment(level)) => {
+                if let Some('*') = lookahead(state) {
+                    pre_processor_state = PreProcessorState::MultiComment(level.saturating_add(1));
                 }
-                _ => {}
-            },
-            (_, PreProcessorState::MultiComment(_)) => {
-                column += 1;
             }
+            (_, PreProcessorState::MultiComment(_)) => {}
         }
     }
-    state.line = line;
-    state.col = column;
+    match result {
+        Some('\n') => state.at_line_start = true,
+        Some(c) if !c.is_whitespace() => state.at_line_start = false,
+        _ => {}
+    }
     result
 }
 
@@ -315,29 +629,29 @@ mod test {
 
     #[test]
     fn successfully_parses_empty_string() {
-        assert_eq!(run(Box::new("".chars())), Ok(vec![]));
+        assert_eq!(run(""), Ok(vec![]));
     }
 
     #[test]
     fn successfully_parses_raw_token() {
         assert_eq!(
-            unwrap_tokens(run(Box::new("test".chars()))),
-            Ok(vec![TokenType::RawLiteral(String::from("test"))])
+            unwrap_tokens(run("test")),
+            Ok(vec![TokenType::RawLiteral(Cow::from("test"))])
         );
     }
 
     #[test]
     fn successfully_parses_string_token() {
         assert_eq!(
-            unwrap_tokens(run(Box::new("\"test\"".chars()))),
-            Ok(vec![TokenType::StringLiteral(String::from("test"))])
+            unwrap_tokens(run("\"test\"")),
+            Ok(vec![TokenType::StringLiteral(Cow::from("test"))])
         );
     }
 
     #[test]
     fn successfully_parse_basic_tokens() {
         assert_eq!(
-            unwrap_tokens(run(Box::new("(){};:".chars()))),
+            unwrap_tokens(run("(){};:")),
             Ok(vec![
                 TokenType::OpenParen,
                 TokenType::CloseParen,
@@ -356,15 +670,13 @@ mod test {
     #[test]
     fn successfully_parse_a_typical_example() {
         assert_eq!(
-            unwrap_tokens(run(Box::new(
-                "option param { inner_option \"value\"; };".chars()
-            ))),
+            unwrap_tokens(run("option param { inner_option \"value\"; };")),
             Ok(vec![
-                TokenType::RawLiteral(String::from("option")),
-                TokenType::RawLiteral(String::from("param")),
+                TokenType::RawLiteral(Cow::from("option")),
+                TokenType::RawLiteral(Cow::from("param")),
                 TokenType::OpenBrace,
-                TokenType::RawLiteral(String::from("inner_option")),
-                TokenType::StringLiteral(String::from("value")),
+                TokenType::RawLiteral(Cow::from("inner_option")),
+                TokenType::StringLiteral(Cow::from("value")),
                 TokenType::Semicolon,
                 TokenType::CloseBrace,
                 TokenType::Semicolon
@@ -375,21 +687,203 @@ mod test {
     #[test]
     fn ignores_comments() {
         assert_eq!(
-            unwrap_tokens(run(Box::new(
-                "/* shit */
+            unwrap_tokens(run("/* shit */
                                        // crap
-                                       # shit"
-                    .chars()
-            ))),
+                                       # shit")),
             Ok(vec![])
         );
     }
 
+    #[test]
+    fn keeps_comment_markers_in_strings() {
+        assert_eq!(
+            unwrap_tokens(run("\"a # b // c /* d\\\\\" # e")),
+            Ok(vec![TokenType::StringLiteral(Cow::from(
+                "a # b // c /* d\\"
+            ))])
+        );
+    }
+
+    #[test]
+    fn records_token_spans() {
+        let tokens = run("totem {\n  token: \"3000\"\n}").unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|t| t.span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 5, 1, 1),
+                Span::new(6, 7, 1, 7),
+                Span::new(10, 15, 2, 3),
+                Span::new(15, 16, 2, 8),
+                Span::new(17, 23, 2, 10),
+                Span::new(23, 24, 2, 16),
+                Span::new(24, 25, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_byte_offsets_after_multibyte_characters() {
+        let tokens = run("é /* ü */ x").unwrap();
+        assert_eq!(tokens[0].span, Span::new(0, 2, 1, 1));
+        assert_eq!(tokens[1].span, Span::new(12, 13, 1, 11));
+    }
+
+    #[test]
+    fn ends_lines_after_quoted_values_and_comments() {
+        assert_eq!(
+            unwrap_tokens(run("a: \"b\"\nc: d # e\nf: g")),
+            Ok(vec![
+                TokenType::RawLiteral(Cow::from("a")),
+                TokenType::Colon,
+                TokenType::StringLiteral(Cow::from("b")),
+                TokenType::LineEnd,
+                TokenType::RawLiteral(Cow::from("c")),
+                TokenType::Colon,
+                TokenType::RawLiteral(Cow::from("d")),
+                TokenType::LineEnd,
+                TokenType::RawLiteral(Cow::from("f")),
+                TokenType::Colon,
+                TokenType::RawLiteral(Cow::from("g")),
+            ])
+        );
+    }
+
     #[test]
     fn fails_on_unterminated_string() {
         assert_eq!(
-            run(Box::new("\"yo dawg".chars())),
+            run("\"yo dawg"),
             Err(Error::new(1, 8, ErrorType::UnexpectedEOF, None))
         );
     }
+
+    #[test]
+    fn resolves_escape_sequences() {
+        assert_eq!(
+            unwrap_tokens(run(r#""\" \\ \n \t \r \x41 \u{e9} \u{1F600}""#)),
+            Ok(vec![TokenType::StringLiteral(Cow::from(
+                "\" \\ \n \t \r A é 😀"
+            ))])
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_escape_sequences() {
+        for (input, sequence) in &[
+            (r#""a\qb""#, r"\q"),
+            (r#""a\x4g""#, r"\x4g"),
+            (r#""a\xff""#, r"\xff"),
+            (r#""a\u41""#, r"\u4"),
+            (r#""a\u{}""#, r"\u{}"),
+            (r#""a\u{d800}""#, r"\u{d800}"),
+            (r#""a\u{1234567}""#, r"\u{1234567"),
+        ] {
+            let err = run(input).unwrap_err();
+            assert_eq!(
+                err.error_type(),
+                &ErrorType::InvalidEscape(sequence.to_string()),
+                "{}",
+                input
+            );
+            assert_eq!((err.line(), err.col()), (1, 3));
+        }
+        assert_eq!(
+            run(r#""a\x4"#).unwrap_err().error_type(),
+            &ErrorType::UnexpectedEOF
+        );
+    }
+
+    #[test]
+    fn borrows_literals_from_the_input() {
+        let tokens = run("name: \"a b\" c/* x */d \"e\\n\"").unwrap();
+        let borrowed: Vec<bool> = tokens
+            .iter()
+            .filter_map(|t| match t.token_type {
+                TokenType::StringLiteral(ref s) | TokenType::RawLiteral(ref s) => {
+                    Some(matches!(s, Cow::Borrowed(_)))
+                }
+                _ => None,
+            })
+            .collect();
+        // Text with a comment or an escape sequence removed has to be copied
+        assert_eq!(borrowed, vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn stops_after_the_first_error() {
+        let mut lexer = Lexer::new("a \"b\\q\" c {");
+        assert_eq!(
+            lexer.next().map(|t| t.map(|t| t.token_type)),
+            Some(Ok(TokenType::RawLiteral(Cow::from("a"))))
+        );
+        assert!(matches!(lexer.next(), Some(Err(_))));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn keeps_colons_after_the_first_in_values() {
+        assert_eq!(
+            unwrap_tokens(run("ring0_addr:fe80::1%eth0 ::1\nname: a:b ; c: d")),
+            Ok(vec![
+                TokenType::RawLiteral(Cow::from("ring0_addr")),
+                TokenType::Colon,
+                TokenType::RawLiteral(Cow::from("fe80::1%eth0")),
+                TokenType::RawLiteral(Cow::from("::1")),
+                TokenType::LineEnd,
+                TokenType::RawLiteral(Cow::from("name")),
+                TokenType::Colon,
+                TokenType::RawLiteral(Cow::from("a:b")),
+                TokenType::Semicolon,
+                TokenType::RawLiteral(Cow::from("c")),
+                TokenType::Colon,
+                TokenType::RawLiteral(Cow::from("d")),
+            ])
+        );
+    }
+
+    #[test]
+    fn reads_line_values_verbatim() {
+        let options = ParseOptions {
+            line_values: true,
+            ..ParseOptions::default()
+        };
+        let tokens = run_with("name:  prod \"a\"\tcluster; \nempty:\n", &options).unwrap();
+        assert_eq!(
+            tokens.iter().map(|t| &t.token_type).collect::<Vec<_>>(),
+            vec![
+                &TokenType::RawLiteral(Cow::from("name")),
+                &TokenType::Colon,
+                &TokenType::RawLiteral(Cow::from("prod \"a\"\tcluster;")),
+                &TokenType::LineEnd,
+                &TokenType::RawLiteral(Cow::from("empty")),
+                &TokenType::Colon,
+                &TokenType::LineEnd,
+            ]
+        );
+        assert_eq!(tokens[2].span, Span::new(7, 24, 1, 8));
+        assert!(matches!(
+            tokens[2].token_type,
+            TokenType::RawLiteral(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn only_reads_hash_comments_at_line_start() {
+        let options = ParseOptions {
+            comments: Comments::corosync(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            unwrap_tokens(run_with("  # a\nb#c // d /* e */\n", &options)),
+            Ok(vec![
+                TokenType::RawLiteral(Cow::from("b#c")),
+                TokenType::RawLiteral(Cow::from("//")),
+                TokenType::RawLiteral(Cow::from("d")),
+                TokenType::RawLiteral(Cow::from("/*")),
+                TokenType::RawLiteral(Cow::from("e")),
+                TokenType::RawLiteral(Cow::from("*/")),
+                TokenType::LineEnd,
+            ])
+        );
+    }
 }
@@ -1,16 +1,145 @@
+pub mod cmap;
+pub mod cmapctl;
 pub mod config;
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diagnostic;
+pub mod effective;
 pub mod error;
 pub mod lexer;
+pub mod model;
 pub mod parser;
+pub mod query;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod span;
+pub mod validate;
+pub mod visitor;
+pub mod writer;
 
-pub use config::ConfigBlock;
+pub use cmapctl::{CmapDump, CmapEntry, Difference};
+pub use config::{BlockKind, ConfigBlock, ConfigNode};
+pub use cst::Document;
+pub use diagnostic::{Diagnostic, Severity};
+pub use effective::{CorosyncVersion, EffectiveConfig, Origin};
 pub use error::{Error as ParseError, Result};
+pub use lexer::Lexer;
+pub use model::CorosyncConfig;
+pub use parser::{Comments, Duplicates, ParseOptions};
+pub use query::{Query, QueryMatch};
+pub use span::Span;
+pub use validate::validate;
+pub use visitor::{Entry, Visitor};
+pub use writer::{FormatOptions, Indent};
 
-pub fn parse(data: String) -> Result<ConfigBlock> {
-    let owned_string = Box::leak(data.into_boxed_str());
+use error::{Error, ErrorType};
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str;
 
-    match lexer::run(Box::new(owned_string.chars())) {
-        Ok(tokens) => parser::run(Box::new(tokens.into_iter())),
-        Err(err) => Err(err),
+/// Where corosync looks for its configuration unless told otherwise
+pub const DEFAULT_CONFIG_FILE: &str = "/etc/corosync/corosync.conf";
+
+/// Environment variable corosync reads to override `DEFAULT_CONFIG_FILE`
+pub const CONFIG_FILE_ENV: &str = "COROSYNC_MAIN_CONFIG_FILE";
+
+/// Parses a config file. The returned tree owns its data and does not borrow
+/// from `data`
+pub fn parse(data: &str) -> Result<ConfigBlock> {
+    parser::run(Lexer::new(data))
+}
+
+/// Parses a config file with explicit options, e.g. to be lenient or to
+/// reject duplicate keys
+pub fn parse_with(data: &str, options: &ParseOptions) -> Result<ConfigBlock> {
+    parser::run_with(Lexer::with_options(data, options), options)
+}
+
+/// Parses a config file the way corosync reads it: values are the rest of
+/// their line and only `#` at the start of a line begins a comment. See
+/// `ParseOptions::corosync`
+pub fn parse_corosync(data: &str) -> Result<ConfigBlock> {
+    parse_with(data, &ParseOptions::corosync())
+}
+
+/// Parses a config file, passing everything found to `visitor` instead of
+/// building a tree. Stops at the first error or when the visitor asks to
+pub fn visit<V: Visitor>(data: &str, options: &ParseOptions, visitor: &mut V) -> Result<()> {
+    parser::visit(Lexer::with_options(data, options), options, visitor)
+}
+
+/// Parses a config file with explicit options and also returns warnings
+/// about things which are valid but likely mistakes, such as stray
+/// semicolons, duplicate keys and empty sections
+pub fn parse_with_diagnostics(
+    data: &str,
+    options: &ParseOptions,
+) -> Result<(ConfigBlock, Vec<Diagnostic>)> {
+    parser::run_with_diagnostics(Lexer::with_options(data, options), options)
+}
+
+/// Parses a config file without stopping at the first error. Returns the
+/// tree that could be parsed, with the blocks containing errors marked as
+/// broken, and every error found in document order
+pub fn parse_recovering(data: &str) -> (ConfigBlock, Vec<Error>) {
+    let options = ParseOptions::default();
+    parser::run_recovering(Lexer::with_options(data, &options), &options)
+}
+
+/// Parses a config file from raw bytes, which must be valid UTF-8
+pub fn parse_bytes(data: &[u8]) -> Result<ConfigBlock> {
+    parse_bytes_with(data, &ParseOptions::default())
+}
+
+/// Like `parse_bytes`, but with explicit options
+pub fn parse_bytes_with(data: &[u8], options: &ParseOptions) -> Result<ConfigBlock> {
+    match str::from_utf8(data) {
+        Ok(text) => parse_with(text, options),
+        Err(err) => {
+            // Everything up to the error is valid, so it can be used to locate it
+            let valid = str::from_utf8(&data[..err.valid_up_to()]).unwrap_or_default();
+            let line = valid.matches('\n').count() as u32 + 1;
+            let col = valid.rsplit('\n').next().unwrap_or("").chars().count() as u32 + 1;
+            Err(Error::new(line, col, ErrorType::InvalidUtf8, None))
+        }
+    }
+}
+
+/// Reads and parses a config file from `reader`
+pub fn parse_reader<R: Read>(mut reader: R) -> Result<ConfigBlock> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    parse_bytes(&data)
+}
+
+/// Reads and parses the config file at `path`. Errors carry the path
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<ConfigBlock> {
+    parse_file_with(path, &ParseOptions::default())
+}
+
+/// Like `parse_file`, but with explicit options
+pub fn parse_file_with<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<ConfigBlock> {
+    let path = path.as_ref();
+    match fs::read(path) {
+        Ok(data) => parse_bytes_with(&data, options).map_err(|err| err.with_path(path)),
+        Err(err) => Err(Error::from(err).with_path(path)),
     }
 }
+
+/// Returns the path of the config file corosync would load: the value of
+/// `COROSYNC_MAIN_CONFIG_FILE` if set, `/etc/corosync/corosync.conf` otherwise
+pub fn default_config_path() -> PathBuf {
+    match env::var_os(CONFIG_FILE_ENV) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(DEFAULT_CONFIG_FILE),
+    }
+}
+
+/// Reads and parses the config file corosync would load, see
+/// `default_config_path`, the way corosync reads it, see `parse_corosync`
+pub fn parse_default() -> Result<ConfigBlock> {
+    parse_file_with(default_config_path(), &ParseOptions::corosync())
+}
@@ -0,0 +1,966 @@
+use super::config::ConfigBlock;
+use super::effective::{self, CorosyncVersion, EffectiveConfig};
+use super::error::{Error, ErrorType, Result};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Conversion between a typed field and the text of an option value
+pub(crate) trait Value: Sized {
+    const EXPECTED: &'static str;
+    fn parse_value(value: &str) -> Option<Self>;
+    fn to_value(&self) -> String;
+}
+
+macro_rules! value_from_str {
+    ($($ty:ty => $expected:expr),*) => {
+        $(impl Value for $ty {
+            const EXPECTED: &'static str = $expected;
+            fn parse_value(value: &str) -> Option<Self> {
+                value.parse().ok()
+            }
+            fn to_value(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+value_from_str!(
+    u8 => "a number between 0 and 255",
+    u16 => "a number between 0 and 65535",
+    u32 => "a number",
+    String => "a value"
+);
+
+impl Value for PathBuf {
+    const EXPECTED: &'static str = "a path";
+    fn parse_value(value: &str) -> Option<Self> {
+        Some(PathBuf::from(value))
+    }
+    fn to_value(&self) -> String {
+        self.display().to_string()
+    }
+}
+
+impl Value for bool {
+    const EXPECTED: &'static str = "yes, no, on or off";
+    fn parse_value(value: &str) -> Option<Self> {
+        parse_bool(value)
+    }
+    fn to_value(&self) -> String {
+        String::from(if *self { "yes" } else { "no" })
+    }
+}
+
+/// Parses a boolean the way corosync does: `yes`, `on`, `true` and `1` are
+/// true, `no`, `off`, `false` and `0` are false
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "on" | "true" | "1" => Some(true),
+        "no" | "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Defines an enum of keywords with conversions from and to their names
+macro_rules! keyword_enum {
+    ($(#[$meta:meta])* $name:ident, $expected:expr, { $($variant:ident => $text:expr),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        pub enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            /// Returns the keyword as written in corosync.conf
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text),*
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ();
+            fn from_str(value: &str) -> std::result::Result<$name, ()> {
+                match value {
+                    $($text => Ok($name::$variant),)*
+                    _ => Err(()),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Value for $name {
+            const EXPECTED: &'static str = $expected;
+            fn parse_value(value: &str) -> Option<Self> {
+                value.parse().ok()
+            }
+            fn to_value(&self) -> String {
+                self.to_string()
+            }
+        }
+    };
+}
+
+keyword_enum!(
+    /// Value of `totem.transport`
+    Transport, "udp, udpu or knet", {
+        Udp => "udp",
+        Udpu => "udpu",
+        Knet => "knet",
+    }
+);
+
+keyword_enum!(
+    /// Value of `totem.crypto_cipher`
+    CryptoCipher, "none, aes256, aes192 or aes128", {
+        None => "none",
+        Aes256 => "aes256",
+        Aes192 => "aes192",
+        Aes128 => "aes128",
+    }
+);
+
+keyword_enum!(
+    /// Value of `totem.crypto_hash`
+    CryptoHash, "none, md5, sha1, sha256, sha384 or sha512", {
+        None => "none",
+        Md5 => "md5",
+        Sha1 => "sha1",
+        Sha256 => "sha256",
+        Sha384 => "sha384",
+        Sha512 => "sha512",
+    }
+);
+
+keyword_enum!(
+    /// Value of `totem.crypto_model`
+    CryptoModel, "nss or openssl", {
+        Nss => "nss",
+        Openssl => "openssl",
+    }
+);
+
+keyword_enum!(
+    /// Value of `totem.ip_version`
+    IpVersion, "ipv4, ipv6, ipv4-6 or ipv6-4", {
+        Ipv4 => "ipv4",
+        Ipv6 => "ipv6",
+        Ipv4Then6 => "ipv4-6",
+        Ipv6Then4 => "ipv6-4",
+    }
+);
+
+keyword_enum!(
+    /// Value of `debug` in `logging` and `logger_subsys`
+    DebugLevel, "on, off or trace", {
+        Off => "off",
+        On => "on",
+        Trace => "trace",
+    }
+);
+
+keyword_enum!(
+    /// Value of `logging.timestamp`
+    Timestamp, "on, off or hires", {
+        Off => "off",
+        On => "on",
+        Hires => "hires",
+    }
+);
+
+/// A node address, which corosync accepts as an IP address or a host name
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Address {
+    Ip(IpAddr),
+    Hostname(String),
+}
+
+impl FromStr for Address {
+    type Err = ();
+    fn from_str(value: &str) -> std::result::Result<Address, ()> {
+        if value.is_empty() {
+            return Err(());
+        }
+        Ok(match value.parse() {
+            Ok(ip) => Address::Ip(ip),
+            Err(_) => Address::Hostname(value.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Ip(ip) => write!(f, "{}", ip),
+            Address::Hostname(name) => f.write_str(name),
+        }
+    }
+}
+
+value_from_str!(Address => "an IP address or host name");
+
+/// A typed view of a whole corosync.conf
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CorosyncConfig {
+    pub totem: Option<Totem>,
+    pub nodelist: Option<Nodelist>,
+    pub quorum: Option<Quorum>,
+    pub logging: Option<Logging>,
+    pub system: Option<System>,
+    pub resources: Option<Resources>,
+    pub nss: Option<Nss>,
+    pub uidgid: Vec<Uidgid>,
+    /// Top level options and sections this model does not know about
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `totem` section. Times are in milliseconds
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Totem {
+    pub version: Option<u32>,
+    pub cluster_name: Option<String>,
+    pub transport: Option<Transport>,
+    pub ip_version: Option<IpVersion>,
+    pub crypto_cipher: Option<CryptoCipher>,
+    pub crypto_hash: Option<CryptoHash>,
+    pub crypto_model: Option<CryptoModel>,
+    pub secauth: Option<bool>,
+    pub keyfile: Option<PathBuf>,
+    pub token: Option<u32>,
+    pub token_coefficient: Option<u32>,
+    pub token_retransmits_before_loss_const: Option<u32>,
+    pub join: Option<u32>,
+    pub consensus: Option<u32>,
+    pub max_messages: Option<u32>,
+    pub interfaces: Vec<Interface>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// An `interface` section inside `totem`
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Interface {
+    pub linknumber: Option<u32>,
+    pub ringnumber: Option<u32>,
+    pub bindnetaddr: Option<Address>,
+    pub mcastaddr: Option<Address>,
+    pub mcastport: Option<u16>,
+    pub ttl: Option<u8>,
+    pub knet_link_priority: Option<u32>,
+    pub knet_ping_interval: Option<u32>,
+    pub knet_ping_timeout: Option<u32>,
+    pub knet_pong_count: Option<u32>,
+    pub knet_transport: Option<String>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `nodelist` section
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Nodelist {
+    pub nodes: Vec<Node>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// A `node` section inside `nodelist`
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Node {
+    pub nodeid: Option<u32>,
+    pub name: Option<String>,
+    /// The `ringX_addr` options, keyed by X
+    pub ring_addrs: BTreeMap<u32, Address>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `quorum` section
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Quorum {
+    pub provider: Option<String>,
+    pub expected_votes: Option<u32>,
+    pub two_node: Option<bool>,
+    pub wait_for_all: Option<bool>,
+    pub last_man_standing: Option<bool>,
+    pub last_man_standing_window: Option<u32>,
+    pub auto_tie_breaker: Option<bool>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `logging` section
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Logging {
+    pub fileline: Option<bool>,
+    pub function_name: Option<bool>,
+    pub timestamp: Option<Timestamp>,
+    pub to_stderr: Option<bool>,
+    pub to_logfile: Option<bool>,
+    pub to_syslog: Option<bool>,
+    pub logfile: Option<PathBuf>,
+    pub logfile_priority: Option<String>,
+    pub syslog_facility: Option<String>,
+    pub syslog_priority: Option<String>,
+    pub debug: Option<DebugLevel>,
+    pub logger_subsys: Vec<LoggerSubsys>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// A `logger_subsys` section inside `logging`
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct LoggerSubsys {
+    pub subsys: Option<String>,
+    pub debug: Option<DebugLevel>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `system` section
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct System {
+    pub sched_rr: Option<bool>,
+    pub priority: Option<String>,
+    pub qb_ipc_type: Option<String>,
+    pub state_dir: Option<PathBuf>,
+    pub move_to_root_cgroup: Option<String>,
+    pub allow_knet_handle_fallback: Option<bool>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `resources` section
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Resources {
+    pub watchdog_timeout: Option<u32>,
+    pub watchdog_device: Option<String>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// The `nss` section. Its options are kept as they are
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Nss {
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// A `uidgid` section, allowing a user or group to connect to corosync
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Uidgid {
+    pub uid: Option<String>,
+    pub gid: Option<String>,
+    pub extra: Vec<ConfigBlock>,
+}
+
+/// Keeps track of which inner blocks of a section were turned into fields
+struct Reader<'a> {
+    block: &'a ConfigBlock,
+    used: Vec<bool>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(block: &'a ConfigBlock) -> Reader<'a> {
+        Reader {
+            block,
+            used: vec![false; block.inner().len()],
+        }
+    }
+
+    /// Returns all inner blocks with the given name, marking them as used
+    fn all(&mut self, key: &str) -> Vec<&'a ConfigBlock> {
+        let block = self.block;
+        block
+            .inner()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.name() == key)
+            .map(|(i, c)| {
+                self.used[i] = true;
+                c
+            })
+            .collect()
+    }
+
+    /// Parses an option. If it is given more than once the last one wins, as
+    /// in corosync
+    fn value<T: Value>(&mut self, key: &str) -> Result<Option<T>> {
+        match self.all(key).last() {
+            Some(option) => parse_option(option).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses all sections with the given name
+    fn sections<T>(&mut self, key: &str) -> Result<Vec<T>>
+    where
+        T: for<'b> TryFrom<&'b ConfigBlock, Error = Error>,
+    {
+        self.all(key).into_iter().map(T::try_from).collect()
+    }
+
+    /// Parses all sections with the given name as one, as corosync merges
+    /// repeated sections. Options given in several of them follow `value`
+    fn section<T>(&mut self, key: &str) -> Result<Option<T>>
+    where
+        T: for<'b> TryFrom<&'b ConfigBlock, Error = Error>,
+    {
+        match self.all(key).as_slice() {
+            [] => Ok(None),
+            [section] => T::try_from(section).map(Some),
+            sections => {
+                let mut merged = ConfigBlock::section(key.to_string(), vec![], vec![]);
+                for section in sections {
+                    for inner in section.inner() {
+                        merged.add_block(inner.clone());
+                    }
+                }
+                T::try_from(&merged).map(Some)
+            }
+        }
+    }
+
+    /// Returns copies of all inner blocks that were not used
+    fn extra(&self) -> Vec<ConfigBlock> {
+        self.block
+            .inner()
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, used)| !**used)
+            .map(|(c, _)| c.clone())
+            .collect()
+    }
+}
+
+fn parse_option<T: Value>(option: &ConfigBlock) -> Result<T> {
+    let value = option.get_opt(0).unwrap_or("");
+    if option.len() <= 1 {
+        if let Some(parsed) = T::parse_value(value) {
+            return Ok(parsed);
+        }
+    }
+    let span = option.param_span(0).or_else(|| option.name_span());
+    let (line, col) = span.map(|s| (s.line, s.col)).unwrap_or((0, 0));
+    Err(Error::new(
+        line,
+        col,
+        ErrorType::InvalidValue(option.name().to_string(), value.to_string()),
+        Some(T::EXPECTED),
+    ))
+}
+
+/// Builds a section from typed fields
+struct Writer {
+    block: ConfigBlock,
+}
+
+impl Writer {
+    fn new(name: &str) -> Writer {
+        Writer {
+            block: ConfigBlock::section(name.to_string(), vec![], vec![]),
+        }
+    }
+
+    fn value<T: Value>(&mut self, key: &str, value: &Option<T>) {
+        if let Some(value) = value {
+            self.block
+                .add_block(ConfigBlock::option(key.to_string(), vec![value.to_value()]));
+        }
+    }
+
+    fn sections<'a, T: 'a, I>(&mut self, sections: I)
+    where
+        I: IntoIterator<Item = &'a T>,
+        ConfigBlock: From<&'a T>,
+    {
+        for section in sections {
+            self.block.add_block(ConfigBlock::from(section));
+        }
+    }
+
+    fn extra(mut self, extra: &[ConfigBlock]) -> ConfigBlock {
+        for block in extra {
+            self.block.add_block(block.clone());
+        }
+        self.block
+    }
+}
+
+impl TryFrom<&ConfigBlock> for CorosyncConfig {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<CorosyncConfig> {
+        let mut r = Reader::new(block);
+        Ok(CorosyncConfig {
+            totem: r.section("totem")?,
+            nodelist: r.section("nodelist")?,
+            quorum: r.section("quorum")?,
+            logging: r.section("logging")?,
+            system: r.section("system")?,
+            resources: r.section("resources")?,
+            nss: r.section("nss")?,
+            uidgid: r.sections("uidgid")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&CorosyncConfig> for ConfigBlock {
+    fn from(config: &CorosyncConfig) -> ConfigBlock {
+        let mut w = Writer {
+            block: ConfigBlock::new(String::new(), vec![], vec![]),
+        };
+        w.sections(&config.totem);
+        w.sections(&config.nodelist);
+        w.sections(&config.quorum);
+        w.sections(&config.logging);
+        w.sections(&config.system);
+        w.sections(&config.resources);
+        w.sections(&config.nss);
+        w.sections(&config.uidgid);
+        w.extra(&config.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Totem {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Totem> {
+        let mut r = Reader::new(block);
+        Ok(Totem {
+            version: r.value("version")?,
+            cluster_name: r.value("cluster_name")?,
+            transport: r.value("transport")?,
+            ip_version: r.value("ip_version")?,
+            crypto_cipher: r.value("crypto_cipher")?,
+            crypto_hash: r.value("crypto_hash")?,
+            crypto_model: r.value("crypto_model")?,
+            secauth: r.value("secauth")?,
+            keyfile: r.value("keyfile")?,
+            token: r.value("token")?,
+            token_coefficient: r.value("token_coefficient")?,
+            token_retransmits_before_loss_const: r.value("token_retransmits_before_loss_const")?,
+            join: r.value("join")?,
+            consensus: r.value("consensus")?,
+            max_messages: r.value("max_messages")?,
+            interfaces: r.sections("interface")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Totem> for ConfigBlock {
+    fn from(totem: &Totem) -> ConfigBlock {
+        let mut w = Writer::new("totem");
+        w.value("version", &totem.version);
+        w.value("cluster_name", &totem.cluster_name);
+        w.value("transport", &totem.transport);
+        w.value("ip_version", &totem.ip_version);
+        w.value("crypto_cipher", &totem.crypto_cipher);
+        w.value("crypto_hash", &totem.crypto_hash);
+        w.value("crypto_model", &totem.crypto_model);
+        w.value("secauth", &totem.secauth);
+        w.value("keyfile", &totem.keyfile);
+        w.value("token", &totem.token);
+        w.value("token_coefficient", &totem.token_coefficient);
+        w.value(
+            "token_retransmits_before_loss_const",
+            &totem.token_retransmits_before_loss_const,
+        );
+        w.value("join", &totem.join);
+        w.value("consensus", &totem.consensus);
+        w.value("max_messages", &totem.max_messages);
+        w.sections(&totem.interfaces);
+        w.extra(&totem.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Interface {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Interface> {
+        let mut r = Reader::new(block);
+        Ok(Interface {
+            linknumber: r.value("linknumber")?,
+            ringnumber: r.value("ringnumber")?,
+            bindnetaddr: r.value("bindnetaddr")?,
+            mcastaddr: r.value("mcastaddr")?,
+            mcastport: r.value("mcastport")?,
+            ttl: r.value("ttl")?,
+            knet_link_priority: r.value("knet_link_priority")?,
+            knet_ping_interval: r.value("knet_ping_interval")?,
+            knet_ping_timeout: r.value("knet_ping_timeout")?,
+            knet_pong_count: r.value("knet_pong_count")?,
+            knet_transport: r.value("knet_transport")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Interface> for ConfigBlock {
+    fn from(interface: &Interface) -> ConfigBlock {
+        let mut w = Writer::new("interface");
+        w.value("linknumber", &interface.linknumber);
+        w.value("ringnumber", &interface.ringnumber);
+        w.value("bindnetaddr", &interface.bindnetaddr);
+        w.value("mcastaddr", &interface.mcastaddr);
+        w.value("mcastport", &interface.mcastport);
+        w.value("ttl", &interface.ttl);
+        w.value("knet_link_priority", &interface.knet_link_priority);
+        w.value("knet_ping_interval", &interface.knet_ping_interval);
+        w.value("knet_ping_timeout", &interface.knet_ping_timeout);
+        w.value("knet_pong_count", &interface.knet_pong_count);
+        w.value("knet_transport", &interface.knet_transport);
+        w.extra(&interface.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Nodelist {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Nodelist> {
+        let mut r = Reader::new(block);
+        Ok(Nodelist {
+            nodes: r.sections("node")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Nodelist> for ConfigBlock {
+    fn from(nodelist: &Nodelist) -> ConfigBlock {
+        let mut w = Writer::new("nodelist");
+        w.sections(&nodelist.nodes);
+        w.extra(&nodelist.extra)
+    }
+}
+
+/// Returns X for a `ringX_addr` option name
+fn ring_number(key: &str) -> Option<u32> {
+    key.strip_prefix("ring")?
+        .strip_suffix("_addr")?
+        .parse()
+        .ok()
+}
+
+impl TryFrom<&ConfigBlock> for Node {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Node> {
+        let mut r = Reader::new(block);
+        let nodeid = r.value("nodeid")?;
+        let name = r.value("name")?;
+        let mut ring_addrs = BTreeMap::new();
+        let rings: Vec<(u32, &str)> = block
+            .inner()
+            .iter()
+            .filter_map(|c| ring_number(c.name()).map(|n| (n, c.name())))
+            .collect();
+        for (number, key) in rings {
+            if let Some(addr) = r.value(key)? {
+                ring_addrs.insert(number, addr);
+            }
+        }
+        Ok(Node {
+            nodeid,
+            name,
+            ring_addrs,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Node> for ConfigBlock {
+    fn from(node: &Node) -> ConfigBlock {
+        let mut w = Writer::new("node");
+        for (number, addr) in &node.ring_addrs {
+            w.value(&format!("ring{}_addr", number), &Some(addr.clone()));
+        }
+        w.value("name", &node.name);
+        w.value("nodeid", &node.nodeid);
+        w.extra(&node.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Quorum {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Quorum> {
+        let mut r = Reader::new(block);
+        Ok(Quorum {
+            provider: r.value("provider")?,
+            expected_votes: r.value("expected_votes")?,
+            two_node: r.value("two_node")?,
+            wait_for_all: r.value("wait_for_all")?,
+            last_man_standing: r.value("last_man_standing")?,
+            last_man_standing_window: r.value("last_man_standing_window")?,
+            auto_tie_breaker: r.value("auto_tie_breaker")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Quorum> for ConfigBlock {
+    fn from(quorum: &Quorum) -> ConfigBlock {
+        let mut w = Writer::new("quorum");
+        w.value("provider", &quorum.provider);
+        w.value("expected_votes", &quorum.expected_votes);
+        w.value("two_node", &quorum.two_node);
+        w.value("wait_for_all", &quorum.wait_for_all);
+        w.value("last_man_standing", &quorum.last_man_standing);
+        w.value("last_man_standing_window", &quorum.last_man_standing_window);
+        w.value("auto_tie_breaker", &quorum.auto_tie_breaker);
+        w.extra(&quorum.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Logging {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Logging> {
+        let mut r = Reader::new(block);
+        Ok(Logging {
+            fileline: r.value("fileline")?,
+            function_name: r.value("function_name")?,
+            timestamp: r.value("timestamp")?,
+            to_stderr: r.value("to_stderr")?,
+            to_logfile: r.value("to_logfile")?,
+            to_syslog: r.value("to_syslog")?,
+            logfile: r.value("logfile")?,
+            logfile_priority: r.value("logfile_priority")?,
+            syslog_facility: r.value("syslog_facility")?,
+            syslog_priority: r.value("syslog_priority")?,
+            debug: r.value("debug")?,
+            logger_subsys: r.sections("logger_subsys")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Logging> for ConfigBlock {
+    fn from(logging: &Logging) -> ConfigBlock {
+        let mut w = Writer::new("logging");
+        w.value("fileline", &logging.fileline);
+        w.value("function_name", &logging.function_name);
+        w.value("timestamp", &logging.timestamp);
+        w.value("to_stderr", &logging.to_stderr);
+        w.value("to_logfile", &logging.to_logfile);
+        w.value("to_syslog", &logging.to_syslog);
+        w.value("logfile", &logging.logfile);
+        w.value("logfile_priority", &logging.logfile_priority);
+        w.value("syslog_facility", &logging.syslog_facility);
+        w.value("syslog_priority", &logging.syslog_priority);
+        w.value("debug", &logging.debug);
+        w.sections(&logging.logger_subsys);
+        w.extra(&logging.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for LoggerSubsys {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<LoggerSubsys> {
+        let mut r = Reader::new(block);
+        Ok(LoggerSubsys {
+            subsys: r.value("subsys")?,
+            debug: r.value("debug")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&LoggerSubsys> for ConfigBlock {
+    fn from(subsys: &LoggerSubsys) -> ConfigBlock {
+        let mut w = Writer::new("logger_subsys");
+        w.value("subsys", &subsys.subsys);
+        w.value("debug", &subsys.debug);
+        w.extra(&subsys.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for System {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<System> {
+        let mut r = Reader::new(block);
+        Ok(System {
+            sched_rr: r.value("sched_rr")?,
+            priority: r.value("priority")?,
+            qb_ipc_type: r.value("qb_ipc_type")?,
+            state_dir: r.value("state_dir")?,
+            move_to_root_cgroup: r.value("move_to_root_cgroup")?,
+            allow_knet_handle_fallback: r.value("allow_knet_handle_fallback")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&System> for ConfigBlock {
+    fn from(system: &System) -> ConfigBlock {
+        let mut w = Writer::new("system");
+        w.value("sched_rr", &system.sched_rr);
+        w.value("priority", &system.priority);
+        w.value("qb_ipc_type", &system.qb_ipc_type);
+        w.value("state_dir", &system.state_dir);
+        w.value("move_to_root_cgroup", &system.move_to_root_cgroup);
+        w.value(
+            "allow_knet_handle_fallback",
+            &system.allow_knet_handle_fallback,
+        );
+        w.extra(&system.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Resources {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Resources> {
+        let mut r = Reader::new(block);
+        Ok(Resources {
+            watchdog_timeout: r.value("watchdog_timeout")?,
+            watchdog_device: r.value("watchdog_device")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Resources> for ConfigBlock {
+    fn from(resources: &Resources) -> ConfigBlock {
+        let mut w = Writer::new("resources");
+        w.value("watchdog_timeout", &resources.watchdog_timeout);
+        w.value("watchdog_device", &resources.watchdog_device);
+        w.extra(&resources.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Nss {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Nss> {
+        Ok(Nss {
+            extra: block.inner().clone(),
+        })
+    }
+}
+
+impl From<&Nss> for ConfigBlock {
+    fn from(nss: &Nss) -> ConfigBlock {
+        Writer::new("nss").extra(&nss.extra)
+    }
+}
+
+impl TryFrom<&ConfigBlock> for Uidgid {
+    type Error = Error;
+    fn try_from(block: &ConfigBlock) -> Result<Uidgid> {
+        let mut r = Reader::new(block);
+        Ok(Uidgid {
+            uid: r.value("uid")?,
+            gid: r.value("gid")?,
+            extra: r.extra(),
+        })
+    }
+}
+
+impl From<&Uidgid> for ConfigBlock {
+    fn from(uidgid: &Uidgid) -> ConfigBlock {
+        let mut w = Writer::new("uidgid");
+        w.value("uid", &uidgid.uid);
+        w.value("gid", &uidgid.gid);
+        w.extra(&uidgid.extra)
+    }
+}
+
+impl CorosyncConfig {
+    /// Builds the typed model from a parsed tree. Fails if a known option
+    /// has a value of the wrong type
+    pub fn from_block(block: &ConfigBlock) -> Result<CorosyncConfig> {
+        CorosyncConfig::try_from(block)
+    }
+
+    /// Converts the model back into a tree, including all unknown options
+    pub fn to_block(&self) -> ConfigBlock {
+        ConfigBlock::from(self)
+    }
+
+    /// Fills in the defaults `version` of corosync uses for every field left
+    /// unset. See `effective::effective`
+    pub fn effective(&self, version: CorosyncVersion) -> EffectiveConfig {
+        effective::effective(&self.to_block(), version)
+    }
+
+    /// Returns the dotted paths of all options and sections the model does not
+    /// know about, e.g. `totem.foo`. Sections which the model does not know
+    /// are reported as a whole
+    pub fn unknown_keys(&self) -> Vec<String> {
+        let mut keys = vec![];
+        let mut add = |prefix: &str, extra: &[ConfigBlock]| {
+            for block in extra {
+                keys.push(if prefix.is_empty() {
+                    block.name().to_string()
+                } else {
+                    format!("{}.{}", prefix, block.name())
+                });
+            }
+        };
+
+        add("", &self.extra);
+        if let Some(ref totem) = self.totem {
+            add("totem", &totem.extra);
+            for interface in &totem.interfaces {
+                add("totem.interface", &interface.extra);
+            }
+        }
+        if let Some(ref nodelist) = self.nodelist {
+            add("nodelist", &nodelist.extra);
+            for node in &nodelist.nodes {
+                add("nodelist.node", &node.extra);
+            }
+        }
+        if let Some(ref quorum) = self.quorum {
+            add("quorum", &quorum.extra);
+        }
+        if let Some(ref logging) = self.logging {
+            add("logging", &logging.extra);
+            for subsys in &logging.logger_subsys {
+                add("logging.logger_subsys", &subsys.extra);
+            }
+        }
+        if let Some(ref system) = self.system {
+            add("system", &system.extra);
+        }
+        if let Some(ref resources) = self.resources {
+            add("resources", &resources.extra);
+        }
+        for uidgid in &self.uidgid {
+            add("uidgid", &uidgid.extra);
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_corosync_booleans() {
+        for value in &["yes", "on", "true", "1", "YES"] {
+            assert_eq!(parse_bool(value), Some(true));
+        }
+        for value in &["no", "off", "false", "0"] {
+            assert_eq!(parse_bool(value), Some(false));
+        }
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(
+            "192.168.1.1".parse(),
+            Ok(Address::Ip("192.168.1.1".parse().unwrap()))
+        );
+        assert_eq!(
+            "node1.example.com".parse(),
+            Ok(Address::Hostname(String::from("node1.example.com")))
+        );
+        assert_eq!("".parse::<Address>(), Err(()));
+    }
+
+    #[test]
+    fn parses_keywords() {
+        assert_eq!("knet".parse(), Ok(Transport::Knet));
+        assert_eq!("ipv4-6".parse(), Ok(IpVersion::Ipv4Then6));
+        assert_eq!(CryptoHash::Sha256.to_string(), "sha256");
+        assert_eq!("tcp".parse::<Transport>(), Err(()));
+    }
+}
@@ -1,172 +1,665 @@
-use super::config::ConfigBlock;
+use super::config::{BlockKind, ConfigBlock};
+use super::diagnostic::{self, Diagnostic};
 use super::error::{CodePosition, Error, ErrorType, Result};
-use super::lexer;
 use super::lexer::{Token, TokenType};
+use super::span::Span;
+use super::visitor::{Entry, Visitor};
+use std::borrow::Cow;
+use std::ops::ControlFlow;
+use std::result;
 
-macro_rules! expect_token {
-    ($state:expr) => {
-        match next($state) {
-            Some(t) => t,
-            None => return fail($state, ErrorType::UnexpectedEOF, "token")
+/// Controls which syntax the lexer and parser accept
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseOptions {
+    /// In lenient mode an unexpected token after the parameters of an option
+    /// ends the option instead of being an error
+    pub strict: bool,
+    /// Whether `;` may terminate an option
+    pub semicolons: bool,
+    /// Whether `(` and `)` may appear in values. If not, they are an error
+    pub parentheses: bool,
+    /// Which comment styles are recognised
+    pub comments: Comments,
+    /// What happens when an option occurs more than once in a section
+    pub duplicates: Duplicates,
+    /// Whether the value of an option is the rest of its line, trimmed, as
+    /// corosync reads it. Quotes, braces and semicolons in it are kept as
+    /// written. Otherwise values are split into parameters at spaces
+    pub line_values: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            strict: true,
+            semicolons: true,
+            parentheses: false,
+            comments: Comments::default(),
+            duplicates: Duplicates::Keep,
+            line_values: false,
         }
-    };
-    ($state:expr, $ty:expr) => {
-        match next($state) {
-            Some(Token {token_type: $ty(..), ..} @ t) => t,
-            Some(t) => return fail($state, ErrorType::Unexpected(t), stringify!($ty))
-            None => return fail($state, ErrorType::UnexpectedEOF, stringify!($ty))
+    }
+}
+
+impl ParseOptions {
+    /// Reads values and comments the way corosync does: a value is the rest
+    /// of its line and only `#` at the start of a line begins a comment, so
+    /// `//` and `#` inside values are kept
+    pub fn corosync() -> ParseOptions {
+        ParseOptions {
+            parentheses: true,
+            comments: Comments::corosync(),
+            line_values: true,
+            ..ParseOptions::default()
+        }
+    }
+}
+
+/// The comment styles recognised by the lexer. Characters of a disabled
+/// style are read as part of values
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Comments {
+    /// `# comment`
+    pub hash: bool,
+    /// `// comment`
+    pub double_slash: bool,
+    /// `/* comment */`
+    pub block: bool,
+    /// Whether `#` only begins a comment at the start of a line, after
+    /// indentation, as in corosync
+    pub hash_only_at_line_start: bool,
+}
+
+impl Default for Comments {
+    fn default() -> Comments {
+        Comments {
+            hash: true,
+            double_slash: true,
+            block: true,
+            hash_only_at_line_start: false,
+        }
+    }
+}
+
+impl Comments {
+    /// Only `#` at the start of a line, as corosync recognises comments
+    pub fn corosync() -> Comments {
+        Comments {
+            hash: true,
+            double_slash: false,
+            block: false,
+            hash_only_at_line_start: true,
         }
     }
 }
 
-struct ParseState {
-    tokens: Box<dyn Iterator<Item = lexer::Token>>,
-    last_token: Option<Token>,
-    force_next: Option<Token>,
-    done: bool,
+/// Policy for options which occur more than once in the same section.
+/// Sections are never affected, as `interface` or `node` are meant to repeat
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Duplicates {
+    /// Keep every occurrence
+    Keep,
+    /// Keep the first occurrence and drop later ones
+    First,
+    /// Keep the value of the last occurrence, at the place of the first one
+    Last,
+    /// Fail with `ErrorType::DuplicateKey`
+    Error,
 }
 
-impl CodePosition for ParseState {
-    fn location(&self) -> (u32, u16) {
-        match self.last_token {
-            Some(ref t) => (t.line, t.col),
+struct ParseState<'a, I: Iterator<Item = Result<Token<'a>>>> {
+    tokens: I,
+    options: ParseOptions,
+    /// Where the last token taken with `next` was
+    last_span: Option<Span>,
+    force_next: Option<Token<'a>>,
+    /// The error that ended the tokens early, if any
+    lexer_error: Option<Error>,
+    /// Errors collected so far when recovering, `None` to stop at the first
+    errors: Option<Vec<Error>>,
+    /// Number of sections currently open
+    depth: usize,
+}
+
+/// Sections nested deeper than this are rejected, so that hostile input can
+/// not overflow the stack. Real configs use three or four levels
+const MAX_DEPTH: usize = 100;
+
+impl<'a, I: Iterator<Item = Result<Token<'a>>>> ParseState<'a, I> {
+    fn new(tokens: I, options: &ParseOptions, recovering: bool) -> ParseState<'a, I> {
+        ParseState {
+            tokens,
+            options: options.clone(),
+            last_span: None,
+            force_next: None,
+            lexer_error: None,
+            errors: if recovering { Some(vec![]) } else { None },
+            depth: 0,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Result<Token<'a>>>> CodePosition for ParseState<'a, I> {
+    fn location(&self) -> (u32, u32) {
+        match self.last_span {
+            Some(span) => (span.line, span.col),
             None => (0, 0),
         }
     }
 }
 
-pub fn run(tokens: Box<dyn Iterator<Item = lexer::Token>>) -> Result<ConfigBlock> {
-    let mut state = ParseState {
-        tokens: tokens,
-        last_token: None,
-        force_next: None,
-        done: false,
-    };
+/// Why parsing stopped before the end of the input
+enum Stop {
+    Error(Error),
+    /// The visitor asked to stop
+    Break,
+}
 
-    parse_block(&mut state, false, String::from(""), vec![])
+impl From<Error> for Stop {
+    fn from(err: Error) -> Stop {
+        Stop::Error(err)
+    }
 }
 
-fn parse_block(
-    state: &mut ParseState,
-    inner: bool,
-    name: String,
-    options: Vec<String>,
+type Flow = result::Result<(), Stop>;
+
+fn check(flow: ControlFlow<()>) -> Flow {
+    match flow {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(Stop::Break),
+    }
+}
+
+pub fn run<'a, T: IntoIterator<Item = Result<Token<'a>>>>(tokens: T) -> Result<ConfigBlock> {
+    run_with(tokens, &ParseOptions::default())
+}
+
+/// Like `run`, but with explicit options. The comment styles and whether
+/// parentheses are accepted are up to the lexer
+pub fn run_with<'a, T: IntoIterator<Item = Result<Token<'a>>>>(
+    tokens: T,
+    options: &ParseOptions,
 ) -> Result<ConfigBlock> {
-    let mut return_value = ConfigBlock::new(name, options, vec![]);
+    run_with_diagnostics(tokens, options).map(|(root, _)| root)
+}
+
+/// Like `run_with`, but also returns warnings about things which are valid
+/// but likely mistakes, such as duplicate keys or empty sections
+pub fn run_with_diagnostics<'a, T: IntoIterator<Item = Result<Token<'a>>>>(
+    tokens: T,
+    options: &ParseOptions,
+) -> Result<(ConfigBlock, Vec<Diagnostic>)> {
+    let mut builder = TreeBuilder::new(options.duplicates, false);
+    visit(tokens, options, &mut builder)?;
+    let (root, mut errors, diagnostics) = builder.finish();
+    match errors.pop() {
+        Some(err) => Err(err),
+        None => Ok((root, diagnostics)),
+    }
+}
+
+/// Parses the tokens without stopping at errors. After an error the parser
+/// skips to the next line end, semicolon or brace and continues from there.
+/// Returns the tree, in which blocks containing errors are marked as broken,
+/// together with all errors found
+pub fn run_recovering<'a, T: IntoIterator<Item = Result<Token<'a>>>>(
+    tokens: T,
+    options: &ParseOptions,
+) -> (ConfigBlock, Vec<Error>) {
+    let mut state = ParseState::new(tokens.into_iter(), options, true);
+    let mut builder = TreeBuilder::new(options.duplicates, true);
+    let result = drive(&mut state, &mut builder);
+    let (root, mut errors, _) = builder.finish();
+    errors.extend(state.errors.unwrap_or_default());
+    // Only nesting too deep can not be recovered from
+    if let Err(err) = result {
+        errors.push(err);
+    }
+    errors.sort_by_key(|e| (e.line(), e.col()));
+    (root, errors)
+}
+
+/// Parses the tokens, passing everything found to `visitor` instead of
+/// building a tree. Stops at the first error or when the visitor asks to
+pub fn visit<'a, T: IntoIterator<Item = Result<Token<'a>>>, V: Visitor>(
+    tokens: T,
+    options: &ParseOptions,
+    visitor: &mut V,
+) -> Result<()> {
+    let mut state = ParseState::new(tokens.into_iter(), options, false);
+    drive(&mut state, visitor)
+}
+
+fn drive<'a, I: Iterator<Item = Result<Token<'a>>>, V: Visitor>(
+    state: &mut ParseState<'a, I>,
+    visitor: &mut V,
+) -> Result<()> {
+    let result = parse_block(state, visitor, false);
+    // An error from the lexer ends the tokens, so whatever the parser made of
+    // the missing rest is only a consequence of it
+    if let Some(err) = state.lexer_error.take() {
+        match state.errors {
+            Some(ref mut errors) => {
+                errors.retain(|e| e.error_type() != &ErrorType::UnexpectedEOF);
+                errors.push(err);
+            }
+            None => return Err(err),
+        }
+    }
+    match result {
+        Ok(()) | Err(Stop::Break) => Ok(()),
+        Err(Stop::Error(err)) => Err(err),
+    }
+}
+
+fn parse_block<'a, I: Iterator<Item = Result<Token<'a>>>, V: Visitor>(
+    state: &mut ParseState<'a, I>,
+    visitor: &mut V,
+    inner: bool,
+) -> Flow {
     loop {
-        let token = if inner {
-            expect_token!(state)
-        } else {
-            match next(state) {
-                Some(t) => t,
-                None => return Ok(return_value),
+        let token = match next(state) {
+            Some(t) => t,
+            None if inner => {
+                let err = error(state, ErrorType::UnexpectedEOF, "}");
+                report(state, err)?;
+                return check(visitor.exit_section(None));
             }
+            None => return Ok(()),
         };
-        match token.clone().token_type {
-            TokenType::RawLiteral(option_name) => {
-                let params = parse_params(state)?;
-                let t = expect_token!(state);
-                match t.token_type {
-                    TokenType::OpenBrace => {
+        match token.token_type {
+            TokenType::RawLiteral(name) => {
+                let (params, end) = parse_params(state, visitor)?;
+                let (params, param_spans) = params.into_iter().unzip();
+                let entry = Entry {
+                    name,
+                    name_span: token.span,
+                    params,
+                    param_spans,
+                    broken: end == ParamsEnd::Error,
+                };
+                // A skipped option has already been consumed up to its end, and
+                // a closing brace after it belongs to the section
+                let take = match lookahead(state) {
+                    Some(t) if t.token_type == TokenType::OpenBrace => true,
+                    Some(t) if t.token_type == TokenType::CloseBrace => false,
+                    _ if end != ParamsEnd::Terminator => false,
+                    _ => true,
+                };
+                let t = if take { next(state) } else { None };
+                match t {
+                    Some(t) if t.token_type == TokenType::OpenBrace => {
                         // Block follows
-                        return_value.add_block(parse_block(state, true, option_name, params)?);
+                        check(visitor.enter_section(&entry, t.span))?;
+                        parse_section(state, visitor)?;
                     }
                     _ => {
                         // No block. In strict mode this will only ever execute for
                         // TokenType::Semicolon as parse_params() will already have
                         // returned an error for other types
-                        return_value.add_block(ConfigBlock::new(option_name, params, vec![]))
+                        check(visitor.option(&entry))?;
                     }
                 }
             }
-            TokenType::CloseBrace if inner => break,
-            TokenType::Semicolon => {}
+            TokenType::CloseBrace if inner => return check(visitor.exit_section(Some(token.span))),
+            // Semicolons ending an option are consumed with it
+            TokenType::Semicolon if state.options.semicolons => {
+                let message = String::from("semicolon does not end an option");
+                let warning = Diagnostic::warning(diagnostic::STRAY_SEMICOLON, message, token.span);
+                check(visitor.diagnostic(warning))?;
+            }
             _ => {
-                return fail(
+                let span = token.span;
+                let is_brace = token.token_type == TokenType::OpenBrace;
+                let stray_close = token.token_type == TokenType::CloseBrace;
+                let err = error(
                     state,
-                    ErrorType::Unexpected(token.clone()),
+                    ErrorType::Unexpected(token.into_owned()),
                     if inner { "option or }" } else { "option" },
-                )
+                );
+                report(state, err)?;
+                // A stray brace is a point to resynchronise at by itself
+                let open_brace = if is_brace {
+                    Some(span)
+                } else if stray_close {
+                    None
+                } else {
+                    skip(state);
+                    match lookahead(state) {
+                        Some(t) if t.token_type == TokenType::OpenBrace => {
+                            next(state).map(|t| t.span)
+                        }
+                        _ => None,
+                    }
+                };
+                // Keep the braces balanced by parsing the section anyway
+                if let Some(open_brace) = open_brace {
+                    let entry = Entry {
+                        name: Cow::Borrowed(""),
+                        name_span: open_brace,
+                        params: vec![],
+                        param_spans: vec![],
+                        broken: true,
+                    };
+                    check(visitor.enter_section(&entry, open_brace))?;
+                    parse_section(state, visitor)?;
+                }
             }
         }
     }
-    Ok(return_value)
 }
 
-fn parse_params(state: &mut ParseState) -> Result<Vec<String>> {
+/// Parses the contents of a section after its opening brace
+fn parse_section<'a, I: Iterator<Item = Result<Token<'a>>>, V: Visitor>(
+    state: &mut ParseState<'a, I>,
+    visitor: &mut V,
+) -> Flow {
+    if state.depth >= MAX_DEPTH {
+        return Err(error(state, ErrorType::TooDeeplyNested, "}").into());
+    }
+    state.depth += 1;
+    let result = parse_block(state, visitor, true);
+    state.depth -= 1;
+    result
+}
+
+/// How the parameters of an option ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ParamsEnd {
+    /// At a token that ends the option or opens its block
+    Terminator,
+    /// At an unexpected token in lenient mode. The rest of the option has
+    /// been skipped
+    Skipped,
+    /// At an error that was recovered from. The rest of the option has been
+    /// skipped
+    Error,
+}
+
+/// Parameters of an option with their spans
+type Params<'a> = Vec<(Cow<'a, str>, Span)>;
+
+/// Parses the parameters of an option
+fn parse_params<'a, I: Iterator<Item = Result<Token<'a>>>, V: Visitor>(
+    state: &mut ParseState<'a, I>,
+    visitor: &mut V,
+) -> result::Result<(Params<'a>, ParamsEnd), Stop> {
     let mut return_value = vec![];
-    loop {
-        match lookahead(state) {
-            Some(t) => match t.token_type {
-                TokenType::StringLiteral(s) => {
-                    return_value.push(s);
-                    pop(state);
+    let semicolons = state.options.semicolons;
+    // The end of the input ends the option like a line end
+    while let Some(t) = lookahead(state) {
+        match t.token_type {
+            TokenType::StringLiteral(_) | TokenType::RawLiteral(_) => {
+                if let Some(Token {
+                    token_type: TokenType::StringLiteral(s) | TokenType::RawLiteral(s),
+                    span,
+                }) = next(state)
+                {
+                    return_value.push((s, span));
+                }
+            }
+            TokenType::OpenBrace => break,
+            TokenType::Semicolon if semicolons => break,
+            TokenType::LineEnd => break,
+            TokenType::Colon => {
+                pop(state);
+            }
+            _ => {
+                // Left in place for `skip`, which stops at braces
+                let t = t.clone().into_owned();
+                if !state.options.strict {
+                    let message = format!("skipped {} and the rest of the option", t.token_type);
+                    let warning = Diagnostic::warning(diagnostic::SKIPPED_TOKENS, message, t.span);
+                    check(visitor.diagnostic(warning))?;
+                    skip(state);
+                    return Ok((return_value, ParamsEnd::Skipped));
+                } else {
+                    let err = error(state, ErrorType::Unexpected(t), "; or {");
+                    report(state, err)?;
+                    skip(state);
+                    return Ok((return_value, ParamsEnd::Error));
                 }
-                TokenType::RawLiteral(s) => {
-                    return_value.push(s);
-                    pop(state);
+            }
+        }
+    }
+    Ok((return_value, ParamsEnd::Terminator))
+}
+
+/// Builds a `ConfigBlock` tree from the parsed contents, applying the
+/// duplicate key policy
+struct TreeBuilder {
+    /// The sections currently open, starting with the root
+    stack: Vec<ConfigBlock>,
+    duplicates: Duplicates,
+    /// Whether errors are collected instead of stopping the parser
+    recovering: bool,
+    errors: Vec<Error>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TreeBuilder {
+    fn new(duplicates: Duplicates, recovering: bool) -> TreeBuilder {
+        TreeBuilder {
+            stack: vec![ConfigBlock::new(String::new(), vec![], vec![])],
+            duplicates,
+            recovering,
+            errors: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn block(entry: &Entry) -> ConfigBlock {
+        let params = entry.params.iter().map(|p| p.to_string()).collect();
+        let mut block = ConfigBlock::new(entry.name.to_string(), params, vec![]);
+        block.positions.name = Some(entry.name_span);
+        block.positions.params = entry.param_spans.iter().copied().map(Some).collect();
+        block.broken = entry.broken;
+        block
+    }
+
+    fn warn(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics
+            .push(Diagnostic::warning(code, message, span));
+    }
+
+    /// Closes the sections left open by an early stop and returns the root
+    fn finish(mut self) -> (ConfigBlock, Vec<Error>, Vec<Diagnostic>) {
+        while self.stack.len() > 1 {
+            let _ = self.exit_section(None);
+        }
+        let root = self
+            .stack
+            .pop()
+            .unwrap_or_else(|| ConfigBlock::new(String::new(), vec![], vec![]));
+        (root, self.errors, self.diagnostics)
+    }
+}
+
+impl Visitor for TreeBuilder {
+    fn enter_section(&mut self, entry: &Entry, open_brace: Span) -> ControlFlow<()> {
+        let mut block = TreeBuilder::block(entry);
+        block.set_kind(BlockKind::Section);
+        block.positions.open_brace = Some(open_brace);
+        self.stack.push(block);
+        ControlFlow::Continue(())
+    }
+
+    fn option(&mut self, entry: &Entry) -> ControlFlow<()> {
+        let mut option = TreeBuilder::block(entry);
+        let section = match self.stack.last_mut() {
+            Some(section) => section,
+            None => return ControlFlow::Continue(()),
+        };
+        let existing = section
+            .inner()
+            .iter()
+            .position(|b| b.name() == option.name() && b.is_option());
+        let i = match existing {
+            Some(i) => i,
+            None => {
+                section.add_block(option);
+                return ControlFlow::Continue(());
+            }
+        };
+        let span = entry.name_span;
+        let name = &entry.name;
+        match self.duplicates {
+            Duplicates::Keep => {
+                section.add_block(option);
+                let message = format!("`{}` is set more than once", name);
+                self.warn(diagnostic::DUPLICATE_KEY, message, span);
+            }
+            Duplicates::First => {
+                let message = format!("`{}` is set again, this value is ignored", name);
+                self.warn(diagnostic::DUPLICATE_KEY, message, span);
+            }
+            Duplicates::Last => {
+                section.inner_mut()[i] = option;
+                let message = format!("`{}` is set again, this value is used", name);
+                self.warn(diagnostic::DUPLICATE_KEY, message, span);
+            }
+            Duplicates::Error => {
+                option.broken = true;
+                section.add_block(option);
+                let key = ErrorType::DuplicateKey(name.to_string());
+                self.errors.push(Error::new(span.line, span.col, key, None));
+                if !self.recovering {
+                    return ControlFlow::Break(());
                 }
-                TokenType::OpenBrace => break,
-                TokenType::Semicolon => break,
-                TokenType::LineEnd => break,
-                TokenType::Colon => {
-                    pop(state);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn exit_section(&mut self, close_brace: Option<Span>) -> ControlFlow<()> {
+        // The root is never closed
+        if self.stack.len() < 2 {
+            return ControlFlow::Continue(());
+        }
+        let mut block = match self.stack.pop() {
+            Some(block) => block,
+            None => return ControlFlow::Continue(()),
+        };
+        // The section opened at a stray brace has no name, so its contents
+        // are lifted into the parent to keep the tree writable
+        if block.name().is_empty() && block.is_broken() {
+            if let Some(parent) = self.stack.last_mut() {
+                for inner in block.inner_mut().drain(..) {
+                    parent.add_block(inner);
                 }
-                _ => {
-                    if cfg!(feature = "nonstrict") {
-                        break;
-                    } else {
-                        println!("Errored params");
-                        return fail(state, ErrorType::Unexpected(t), "; or {");
-                    }
+            }
+            return ControlFlow::Continue(());
+        }
+        match close_brace {
+            Some(span) => {
+                block.positions.close_brace = Some(span);
+                if block.inner().is_empty() {
+                    let message = format!("section `{}` is empty", block.name());
+                    let span = block.span().unwrap_or(span);
+                    self.warn(diagnostic::EMPTY_SECTION, message, span);
                 }
-            },
-            None => return fail(&state, ErrorType::UnexpectedEOF, "}"),
+            }
+            None => block.broken = true,
         }
+        if let Some(parent) = self.stack.last_mut() {
+            parent.add_block(block);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn diagnostic(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        self.diagnostics.push(diagnostic);
+        ControlFlow::Continue(())
     }
-    Ok(return_value)
 }
 
-fn next(state: &mut ParseState) -> Option<lexer::Token> {
-    let v = match &state.force_next {
-        &Some(ref t) => Some(t.clone()),
-        &None => state.tokens.next(),
-    };
-    state.force_next = None;
-    match v.clone() {
-        Some(_) => {}
-        None => {
-            if state.done {
-                unreachable!("Tried to get another token after end of stream");
-            } else {
-                state.done = true;
+/// Skips tokens up to the next line end, semicolon or brace. Line ends and
+/// semicolons are consumed, braces are left for the caller
+fn skip<'a, I: Iterator<Item = Result<Token<'a>>>>(state: &mut ParseState<'a, I>) {
+    while let Some(t) = lookahead(state) {
+        match t.token_type {
+            TokenType::LineEnd | TokenType::Semicolon => {
+                pop(state);
+                break;
             }
+            TokenType::OpenBrace | TokenType::CloseBrace => break,
+            _ => pop(state),
         }
     }
-    v
 }
 
-fn pop(state: &mut ParseState) {
-    if state.force_next.is_some() {
-        state.force_next = None;
-    } else {
-        state.tokens.next();
+/// Records `err` when recovering and returns it otherwise. Running out of
+/// tokens is only recorded once, no matter how many sections it leaves open
+fn report<'a, I: Iterator<Item = Result<Token<'a>>>>(
+    state: &mut ParseState<'a, I>,
+    err: Error,
+) -> Result<()> {
+    match state.errors {
+        Some(ref mut errors) => {
+            let eof = ErrorType::UnexpectedEOF;
+            if err.error_type() != &eof || errors.iter().all(|e| e.error_type() != &eof) {
+                errors.push(err);
+            }
+            Ok(())
+        }
+        None => Err(err),
     }
 }
 
-fn lookahead(state: &mut ParseState) -> Option<lexer::Token> {
-    match state.force_next.clone() {
+fn next<'a, I: Iterator<Item = Result<Token<'a>>>>(
+    state: &mut ParseState<'a, I>,
+) -> Option<Token<'a>> {
+    let v = match state.force_next.take() {
         Some(t) => Some(t),
-        None => {
-            let t = state.tokens.next();
-            state.force_next = t.clone();
-            t
+        None => pull(state),
+    };
+    if let Some(ref t) = v {
+        state.last_span = Some(t.span);
+    }
+    v
+}
+
+fn pop<'a, I: Iterator<Item = Result<Token<'a>>>>(state: &mut ParseState<'a, I>) {
+    next(state);
+}
+
+fn lookahead<'s, 'a, I: Iterator<Item = Result<Token<'a>>>>(
+    state: &'s mut ParseState<'a, I>,
+) -> Option<&'s Token<'a>> {
+    if state.force_next.is_none() {
+        state.force_next = pull(state);
+    }
+    state.force_next.as_ref()
+}
+
+/// Takes the next token from the lexer. An error ends the tokens and is kept
+/// for `drive` to report
+fn pull<'a, I: Iterator<Item = Result<Token<'a>>>>(
+    state: &mut ParseState<'a, I>,
+) -> Option<Token<'a>> {
+    if state.lexer_error.is_some() {
+        return None;
+    }
+    match state.tokens.next() {
+        Some(Ok(t)) => Some(t),
+        Some(Err(err)) => {
+            state.lexer_error = Some(err);
+            None
         }
+        None => None,
     }
 }
 
-fn fail<T>(state: &ParseState, error_type: ErrorType, expected: &'static str) -> Result<T> {
-    Err(Error::from_state(state, error_type, Some(expected)))
+fn error<'a, I: Iterator<Item = Result<Token<'a>>>>(
+    state: &ParseState<'a, I>,
+    error_type: ErrorType,
+    expected: &'static str,
+) -> Error {
+    let location = match error_type {
+        ErrorType::Unexpected(ref t) => t.location(),
+        _ => state.location(),
+    };
+    Error::new(location.0, location.1, error_type, Some(expected))
 }
 
 #[cfg(test)]
@@ -178,13 +671,10 @@ mod test {
     #[test]
     fn test_it_parsing_the_most_basic_option() {
         assert_eq!(
-            run(Box::new(
-                vec![
-                    tok(TokenType::RawLiteral(String::from("test"))),
-                    tok(TokenType::Semicolon)
-                ]
-                .into_iter()
-            )),
+            run(vec![
+                tok(TokenType::RawLiteral(Cow::from("test"))),
+                tok(TokenType::Semicolon)
+            ]),
             Ok(ConfigBlock::new(
                 String::new(),
                 vec![],
@@ -196,18 +686,15 @@ mod test {
     #[test]
     fn test_it_parsing_a_typical_example() {
         assert_eq!(
-            run(Box::new(
-                vec![
-                    tok(TokenType::RawLiteral(String::from("option"))),
-                    tok(TokenType::RawLiteral(String::from("param1"))),
-                    tok(TokenType::OpenBrace),
-                    tok(TokenType::RawLiteral(String::from("inner"))),
-                    tok(TokenType::StringLiteral(String::from("value"))),
-                    tok(TokenType::Semicolon),
-                    tok(TokenType::CloseBrace),
-                ]
-                .into_iter()
-            )),
+            run(vec![
+                tok(TokenType::RawLiteral(Cow::from("option"))),
+                tok(TokenType::RawLiteral(Cow::from("param1"))),
+                tok(TokenType::OpenBrace),
+                tok(TokenType::RawLiteral(Cow::from("inner"))),
+                tok(TokenType::StringLiteral(Cow::from("value"))),
+                tok(TokenType::Semicolon),
+                tok(TokenType::CloseBrace),
+            ]),
             Ok(ConfigBlock::new(
                 String::new(),
                 vec![],
@@ -224,7 +711,27 @@ mod test {
         );
     }
 
-    fn tok(ty: TokenType) -> Token {
-        Token::new(0, 0, ty)
+    #[test]
+    fn test_it_parsing_an_option_at_the_end_of_input() {
+        assert_eq!(
+            run(vec![
+                tok(TokenType::RawLiteral(Cow::from("test"))),
+                tok(TokenType::Colon),
+                tok(TokenType::RawLiteral(Cow::from("value"))),
+            ]),
+            Ok(ConfigBlock::new(
+                String::new(),
+                vec![],
+                vec![ConfigBlock::new(
+                    String::from("test"),
+                    vec![String::from("value")],
+                    vec![]
+                )]
+            ))
+        );
+    }
+
+    fn tok(ty: TokenType) -> Result<Token> {
+        Ok(Token::new(Span::default(), ty))
     }
 }
@@ -0,0 +1,211 @@
+use super::config::ConfigBlock;
+use super::error::{Error, ErrorType, Result};
+use super::span::Span;
+
+/// A compiled path query such as `nodelist.node[nodeid=2].ring0_addr`.
+///
+/// A query is a list of names separated by dots, each of which selects every
+/// inner block with that name, or every inner block for `*`. A name can be
+/// followed by selectors in brackets, which are applied in order:
+///
+/// * `[N]` keeps the N-th of the blocks selected so far, starting at 0
+/// * `[*]` keeps all of them
+/// * `[key=value]` keeps those with an inner option `key` whose first
+///   parameter is `value`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Segment {
+    name: Option<String>,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Selector {
+    Index(usize),
+    All,
+    Equals(String, String),
+}
+
+/// A block matched by a query
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QueryMatch<'a> {
+    /// The matched block
+    pub block: &'a ConfigBlock,
+    /// Indices into the inner blocks on every level leading to the match
+    pub location: Vec<usize>,
+}
+
+impl<'a> QueryMatch<'a> {
+    /// Returns the first parameter of the matched block, if any
+    pub fn value(&self) -> Option<&'a str> {
+        self.block.get_opt(0)
+    }
+
+    /// Returns the source span of the matched block, if it was parsed
+    pub fn span(&self) -> Option<Span> {
+        self.block.span()
+    }
+}
+
+impl Query {
+    /// Compiles a query. Errors point at the offending column of `query`
+    pub fn parse(query: &str) -> Result<Query> {
+        let chars: Vec<char> = query.chars().collect();
+        let mut segments = vec![];
+        let mut i = 0;
+        loop {
+            let start = i;
+            while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            let name = match name.as_str() {
+                "" => return fail(i, "name is empty", Some("name or *")),
+                "*" => None,
+                _ => Some(name),
+            };
+
+            let mut selectors = vec![];
+            while i < chars.len() && chars[i] == '[' {
+                let start = i + 1;
+                let end = match chars[start..].iter().position(|c| *c == ']') {
+                    Some(len) => start + len,
+                    None => return fail(chars.len(), "unterminated selector", Some("]")),
+                };
+                let content: String = chars[start..end].iter().collect();
+                selectors.push(parse_selector(content.trim(), start)?);
+                i = end + 1;
+            }
+            segments.push(Segment { name, selectors });
+
+            match chars.get(i) {
+                None => break,
+                Some('.') => i += 1,
+                Some(_) => return fail(i, "unexpected character", Some(". or [")),
+            }
+        }
+        Ok(Query { segments })
+    }
+
+    /// Returns every block below `root` matched by the query, in document order
+    pub fn matches<'a>(&self, root: &'a ConfigBlock) -> Vec<QueryMatch<'a>> {
+        let mut current = vec![QueryMatch {
+            block: root,
+            location: vec![],
+        }];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for parent in current {
+                let mut candidates: Vec<(usize, &ConfigBlock)> = parent
+                    .block
+                    .inner()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| match segment.name {
+                        Some(ref name) => c.name() == name,
+                        None => true,
+                    })
+                    .collect();
+                for selector in &segment.selectors {
+                    candidates = apply(selector, candidates);
+                }
+                next.extend(candidates.into_iter().map(|(i, block)| {
+                    let mut location = parent.location.clone();
+                    location.push(i);
+                    QueryMatch { block, location }
+                }));
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+fn parse_selector(content: &str, col: usize) -> Result<Selector> {
+    if content == "*" {
+        return Ok(Selector::All);
+    }
+    if let Ok(index) = content.parse::<usize>() {
+        return Ok(Selector::Index(index));
+    }
+    match content.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok(Selector::Equals(
+            key.trim().to_string(),
+            value.trim().trim_matches('"').to_string(),
+        )),
+        _ => fail(col, "invalid selector", Some("index, * or key=value")),
+    }
+}
+
+fn apply<'a>(
+    selector: &Selector,
+    candidates: Vec<(usize, &'a ConfigBlock)>,
+) -> Vec<(usize, &'a ConfigBlock)> {
+    match selector {
+        Selector::All => candidates,
+        Selector::Index(index) => candidates.into_iter().skip(*index).take(1).collect(),
+        Selector::Equals(key, value) => candidates
+            .into_iter()
+            .filter(|(_, c)| c.matching(key).any(|o| o.get_opt(0) == Some(value)))
+            .collect(),
+    }
+}
+
+fn fail<T>(index: usize, message: &str, expected: Option<&'static str>) -> Result<T> {
+    Err(Error::new(
+        1,
+        index as u32 + 1,
+        ErrorType::InvalidQuery(message.to_string()),
+        expected,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_selectors() {
+        assert_eq!(
+            Query::parse("nodelist.node[nodeid=2][0].*[*]"),
+            Ok(Query {
+                segments: vec![
+                    Segment {
+                        name: Some(String::from("nodelist")),
+                        selectors: vec![],
+                    },
+                    Segment {
+                        name: Some(String::from("node")),
+                        selectors: vec![
+                            Selector::Equals(String::from("nodeid"), String::from("2")),
+                            Selector::Index(0),
+                        ],
+                    },
+                    Segment {
+                        name: None,
+                        selectors: vec![Selector::All],
+                    },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_queries() {
+        let err = Query::parse("totem..token").unwrap_err();
+        assert_eq!(
+            err.error_type(),
+            &ErrorType::InvalidQuery(String::from("name is empty"))
+        );
+        assert_eq!(err.col(), 7);
+
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("totem.interface[1").is_err());
+        assert!(Query::parse("totem.interface[x]").is_err());
+        assert!(Query::parse("totem.interface[1]x").is_err());
+    }
+}
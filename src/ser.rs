@@ -0,0 +1,351 @@
+//! Serialization of any type implementing `serde::Serialize` into
+//! corosync.conf syntax.
+//!
+//! Structs and maps become sections and scalars become `key: value` options.
+//! A `Vec` of structs becomes repeated sections and a `Vec` of scalars
+//! repeated options. `None` values are left out and booleans are written as
+//! `yes`/`no`. The value is first built as a `ConfigBlock`, which is then
+//! written with the crate's writer.
+//!
+//! By default values are quoted where needed, so the output parses back to
+//! the same tree with `parse` and `de::from_str`. corosync keeps quotes as
+//! part of a value, so to write a file for corosync or `parse_corosync`, pass
+//! `FormatOptions::corosync()` to `to_string_with`, which writes values
+//! verbatim.
+
+use super::config::ConfigBlock;
+use super::error::{Error, ErrorType, Result};
+use super::writer::{self, FormatOptions};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt;
+
+/// Serializes `value`, which must be a struct or map, into a config tree
+pub fn to_block<T: Serialize + ?Sized>(value: &T) -> Result<ConfigBlock> {
+    match value.serialize(ValueSerializer)? {
+        Value::Section(inner) => Ok(ConfigBlock::new(String::new(), vec![], inner)),
+        _ => Err(custom("the top level value must be a struct or map")),
+    }
+}
+
+/// Serializes `value`, which must be a struct or map, into corosync.conf text
+/// that `parse` reads back. See the module documentation
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    to_string_with(value, &FormatOptions::default())
+}
+
+/// Like `to_string`, but with explicit formatting options
+pub fn to_string_with<T: Serialize + ?Sized>(value: &T, options: &FormatOptions) -> Result<String> {
+    writer::write(&to_block(value)?, options)
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        custom(msg)
+    }
+}
+
+fn custom<T: fmt::Display>(msg: T) -> Error {
+    Error::new(0, 0, ErrorType::Custom(msg.to_string()), None)
+}
+
+/// What a Rust value turns into before it gets a key
+enum Value {
+    Scalar(String),
+    Section(Vec<ConfigBlock>),
+    List(Vec<Value>),
+    Nothing,
+}
+
+/// Adds `value` under `key` to the inner blocks of a section
+fn push(inner: &mut Vec<ConfigBlock>, key: String, value: Value) -> Result<()> {
+    if key.is_empty() || writer::quote(&key) != key {
+        return Err(custom(format!("`{}` can not be used as a key", key)));
+    }
+    match value {
+        Value::Scalar(s) => inner.push(ConfigBlock::option(key, vec![s])),
+        Value::Section(children) => inner.push(ConfigBlock::section(key, vec![], children)),
+        Value::List(items) => {
+            for item in items {
+                if let Value::List(_) = item {
+                    return Err(custom(format!("`{}` contains a nested sequence", key)));
+                }
+                push(inner, key.clone(), item)?;
+            }
+        }
+        Value::Nothing => {}
+    }
+    Ok(())
+}
+
+struct ValueSerializer;
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<Value> {
+            Ok(Value::Scalar(v.to_string()))
+        })*
+    };
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = SectionSerializer;
+    type SerializeStruct = SectionSerializer;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    serialize_display!(
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str
+    );
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Scalar(String::from(if v { "yes" } else { "no" })))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Value> {
+        Err(custom("byte arrays are not supported"))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Nothing)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Nothing)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Nothing)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::Scalar(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        let mut inner = vec![];
+        push(&mut inner, variant.to_string(), value.serialize(self)?)?;
+        Ok(Value::Section(inner))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<Value, Error>> {
+        Err(custom(format!(
+            "tuple variant `{}` is not supported",
+            variant
+        )))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SectionSerializer> {
+        Ok(SectionSerializer {
+            inner: vec![],
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SectionSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<Value, Error>> {
+        Err(custom(format!(
+            "struct variant `{}` is not supported",
+            variant
+        )))
+    }
+}
+
+struct ListSerializer {
+    items: Vec<Value>,
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::List(self.items))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SectionSerializer {
+    inner: Vec<ConfigBlock>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SectionSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Value::Scalar(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(custom("map keys must be strings or numbers")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().unwrap_or_default();
+        push(&mut self.inner, key, value.serialize(ValueSerializer)?)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Section(self.inner))
+    }
+}
+
+impl ser::SerializeStruct for SectionSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        push(
+            &mut self.inner,
+            key.to_string(),
+            value.serialize(ValueSerializer)?,
+        )
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Section(self.inner))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Quorum {
+        provider: &'static str,
+        two_node: bool,
+        expected_votes: Option<u32>,
+    }
+
+    #[test]
+    fn serializes_scalars() {
+        let quorum = Quorum {
+            provider: "corosync_votequorum",
+            two_node: true,
+            expected_votes: None,
+        };
+        assert_eq!(
+            to_string(&quorum).unwrap(),
+            "provider: corosync_votequorum\ntwo_node: yes\n"
+        );
+    }
+
+    #[test]
+    fn rejects_values_without_keys() {
+        assert!(to_block(&5).is_err());
+        assert!(to_block(&vec![vec![1]]).is_err());
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("bad key", 1);
+        assert!(to_block(&map).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use super::config::ConfigBlock;
+use super::diagnostic::{self, Diagnostic};
+use super::model::{CryptoCipher, CryptoHash, Transport, Value};
+use super::span::Span;
+
+/// Top level sections corosync knows about
+const SECTIONS: &[&str] = &[
+    "totem",
+    "logging",
+    "quorum",
+    "nodelist",
+    "system",
+    "resources",
+    "nss",
+    "uidgid",
+    "nozzle",
+];
+
+/// Options of `totem` which corosync reads as numbers
+const TOTEM_NUMBERS: &[&str] = &[
+    "version",
+    "config_version",
+    "token",
+    "token_coefficient",
+    "token_retransmit",
+    "hold",
+    "token_retransmits_before_loss_const",
+    "join",
+    "send_join",
+    "consensus",
+    "merge",
+    "downcheck",
+    "fail_recv_const",
+    "seqno_unchanged_const",
+    "heartbeat_failures_allowed",
+    "max_network_delay",
+    "window_size",
+    "max_messages",
+    "miss_count_const",
+    "netmtu",
+    "knet_pmtud_interval",
+];
+
+/// Checks `block`, the root of a parsed config, for what corosync would
+/// reject or ignore at startup. Problems corosync refuses to start with are
+/// errors, likely mistakes it works around are warnings. Diagnostics are
+/// returned in document order and point at the offending option or section
+pub fn validate(block: &ConfigBlock) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        diagnostics: vec![],
+    };
+    validator.sections(block);
+    let totem = block.sections().filter(|s| s.name() == "totem").last();
+    let transport = match totem {
+        Some(totem) => validator.totem(totem),
+        None => Transport::Knet,
+    };
+    validator.nodelist(block, totem, transport);
+    let mut diagnostics = validator.diagnostics;
+    diagnostics.sort_by_key(|d| (d.span.line, d.span.col));
+    diagnostics
+}
+
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn warn(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics
+            .push(Diagnostic::warning(code, message, span));
+    }
+
+    fn error(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics
+            .push(Diagnostic::error(code, message, span));
+    }
+
+    fn sections(&mut self, root: &ConfigBlock) {
+        for section in root.sections() {
+            if !SECTIONS.contains(&section.name()) {
+                let message = format!("unknown section `{}`", section.name());
+                self.warn(diagnostic::UNKNOWN_SECTION, message, name_span(section));
+            }
+        }
+    }
+
+    /// Checks the `totem` section and returns the transport it selects
+    fn totem(&mut self, totem: &ConfigBlock) -> Transport {
+        for option in totem.options() {
+            if TOTEM_NUMBERS.contains(&option.name()) {
+                self.value::<u32>(option);
+            }
+        }
+        let transport = option(totem, "transport")
+            .and_then(|o| self.value::<Transport>(o))
+            .unwrap_or(Transport::Knet);
+
+        let cipher = option(totem, "crypto_cipher").and_then(|o| self.value::<CryptoCipher>(o));
+        let hash = option(totem, "crypto_hash").and_then(|o| self.value::<CryptoHash>(o));
+        if let Some(cipher) = cipher {
+            if cipher != CryptoCipher::None && hash.unwrap_or(CryptoHash::None) == CryptoHash::None
+            {
+                let message = format!(
+                    "crypto_cipher `{}` needs a crypto_hash other than none",
+                    cipher
+                );
+                let span = option(totem, "crypto_cipher")
+                    .map(value_span)
+                    .unwrap_or_default();
+                self.error(diagnostic::CRYPTO_MISMATCH, message, span);
+            }
+        }
+
+        if transport == Transport::Knet {
+            for interface in totem.sections().filter(|s| s.name() == "interface") {
+                if let Some(bindnetaddr) = option(interface, "bindnetaddr") {
+                    let message =
+                        String::from("bindnetaddr is ignored by knet, which uses the nodelist");
+                    let span = name_span(bindnetaddr);
+                    self.warn(diagnostic::BINDNETADDR_IGNORED, message, span);
+                }
+            }
+        }
+        transport
+    }
+
+    fn nodelist(&mut self, root: &ConfigBlock, totem: Option<&ConfigBlock>, transport: Transport) {
+        let nodes: Vec<&ConfigBlock> = root
+            .sections()
+            .filter(|s| s.name() == "nodelist")
+            .flat_map(|n| n.sections())
+            .filter(|s| s.name() == "node")
+            .collect();
+        if nodes.is_empty() && transport != Transport::Udp {
+            let message = format!(
+                "transport `{}` needs a nodelist with at least one node",
+                transport
+            );
+            let span = totem
+                .and_then(|t| {
+                    option(t, "transport")
+                        .map(value_span)
+                        .or_else(|| t.name_span())
+                })
+                .unwrap_or_default();
+            self.error(diagnostic::MISSING_NODELIST, message, span);
+        }
+
+        let mut seen: Vec<u32> = vec![];
+        for node in nodes {
+            let nodeid = match option(node, "nodeid") {
+                Some(nodeid) => nodeid,
+                None => {
+                    if transport == Transport::Knet {
+                        let message = String::from("node has no nodeid, which knet needs");
+                        self.error(diagnostic::MISSING_NODEID, message, name_span(node));
+                    }
+                    continue;
+                }
+            };
+            match self.value::<u32>(nodeid) {
+                Some(0) => {
+                    let message = String::from("nodeid 0 is reserved");
+                    self.error(diagnostic::INVALID_NODEID, message, value_span(nodeid));
+                }
+                Some(id) if seen.contains(&id) => {
+                    let message = format!("nodeid {} is used by more than one node", id);
+                    self.error(diagnostic::DUPLICATE_NODEID, message, value_span(nodeid));
+                }
+                Some(id) => seen.push(id),
+                None => {}
+            }
+        }
+    }
+
+    /// Parses the value of `option`, reporting it if corosync could not
+    fn value<T: Value>(&mut self, option: &ConfigBlock) -> Option<T> {
+        let value = option.get_opt(0).unwrap_or("");
+        let parsed = if option.len() <= 1 {
+            T::parse_value(value)
+        } else {
+            None
+        };
+        if parsed.is_none() {
+            let message = format!(
+                "`{}` must be {}, not {:?}",
+                option.name(),
+                T::EXPECTED,
+                value
+            );
+            self.error(diagnostic::INVALID_VALUE, message, value_span(option));
+        }
+        parsed
+    }
+}
+
+/// Returns the last option called `name`, which is the one corosync uses
+fn option<'a>(section: &'a ConfigBlock, name: &str) -> Option<&'a ConfigBlock> {
+    section.options().filter(|o| o.name() == name).last()
+}
+
+fn name_span(block: &ConfigBlock) -> Span {
+    block.name_span().unwrap_or_default()
+}
+
+fn value_span(option: &ConfigBlock) -> Span {
+    option
+        .param_span(0)
+        .or_else(|| option.name_span())
+        .unwrap_or_default()
+}
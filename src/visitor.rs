@@ -0,0 +1,52 @@
+use super::diagnostic::Diagnostic;
+use super::span::Span;
+use std::borrow::Cow;
+use std::ops::ControlFlow;
+
+/// An option or the header of a section, as passed to a `Visitor`. Names and
+/// values borrow from the input where possible
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry<'a> {
+    pub name: Cow<'a, str>,
+    pub name_span: Span,
+    pub params: Vec<Cow<'a, str>>,
+    pub param_spans: Vec<Span>,
+    /// Set when a recovering parse found an error in the entry
+    pub(crate) broken: bool,
+}
+
+impl Entry<'_> {
+    /// Returns the first parameter, if any
+    pub fn value(&self) -> Option<&str> {
+        self.params.first().map(|p| p.as_ref())
+    }
+}
+
+/// Receives the contents of a config file while it is parsed, without a tree
+/// being built. Every callback can return `ControlFlow::Break` to stop
+/// parsing early, e.g. once the value looked for has been found.
+///
+/// All callbacks do nothing by default, so only the interesting ones need to
+/// be implemented
+pub trait Visitor {
+    /// Called for the header of a section, before its contents
+    fn enter_section(&mut self, _entry: &Entry, _open_brace: Span) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called for an option with its values
+    fn option(&mut self, _entry: &Entry) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after the contents of a section. The closing brace is only
+    /// missing if a recovering parse ran out of input
+    fn exit_section(&mut self, _close_brace: Option<Span>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called for things which are valid but likely mistakes
+    fn diagnostic(&mut self, _diagnostic: Diagnostic) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
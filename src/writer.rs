@@ -0,0 +1,253 @@
+use super::config::ConfigBlock;
+use super::error::{Error, ErrorType, Result};
+use std::fmt;
+
+/// How nested blocks are indented
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+/// Controls how a `ConfigBlock` is written back to corosync.conf syntax
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FormatOptions {
+    pub indent: Indent,
+    /// Write option values verbatim up to the end of the line, the way
+    /// corosync and `ParseOptions::line_values` read them. Values are never
+    /// quoted, so options with several values, an empty value or a value with
+    /// a line break or surrounding whitespace can not be written
+    pub line_values: bool,
+}
+
+impl FormatOptions {
+    /// Options writing text that corosync and `parse_corosync` read back as
+    /// the same tree
+    pub fn corosync() -> FormatOptions {
+        FormatOptions {
+            line_values: true,
+            ..FormatOptions::default()
+        }
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: Indent::Tabs,
+            line_values: false,
+        }
+    }
+}
+
+/// Writes `block` in corosync.conf syntax. A block without a name is treated
+/// as the document root, so only its inner blocks are written. Fails if a
+/// name would not be read back as one, or with `line_values` if a value can
+/// not be written verbatim
+pub fn write(block: &ConfigBlock, options: &FormatOptions) -> Result<String> {
+    if block.name().is_empty() {
+        for inner in block.inner() {
+            check_names(inner)?;
+        }
+    } else {
+        check_names(block)?;
+    }
+    write_tree(block, options)
+}
+
+fn write_tree(block: &ConfigBlock, options: &FormatOptions) -> Result<String> {
+    let mut out = String::new();
+    if block.name().is_empty() {
+        for inner in block.inner() {
+            write_block(&mut out, inner, options, 0)?;
+        }
+    } else {
+        write_block(&mut out, block, options, 0)?;
+    }
+    Ok(out)
+}
+
+/// Checks that the names of `block` and its inner blocks are read back as
+/// names. They are never quoted, as a quoted name is not read as a key
+fn check_names(block: &ConfigBlock) -> Result<()> {
+    if quote(block.name()) != block.name() {
+        let etype = ErrorType::Custom(format!("`{}` can not be written as a name", block.name()));
+        return Err(unwritable(
+            block,
+            etype,
+            "a name without whitespace, quotes or syntax characters",
+        ));
+    }
+    block.inner().iter().try_for_each(check_names)
+}
+
+fn write_block(
+    out: &mut String,
+    block: &ConfigBlock,
+    options: &FormatOptions,
+    depth: usize,
+) -> Result<()> {
+    write_indent(out, options, depth);
+    out.push_str(block.name());
+    if block.is_section() {
+        for i in 0..block.len() {
+            out.push(' ');
+            out.push_str(&quote(block.get(i)));
+        }
+        out.push_str(" {\n");
+        for inner in block.inner() {
+            write_block(out, inner, options, depth + 1)?;
+        }
+        write_indent(out, options, depth);
+        out.push_str("}\n");
+    } else if options.line_values {
+        // The rest of the line is read back as a single value
+        out.push(':');
+        let value = (0..block.len())
+            .map(|i| block.get(i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let verbatim = !value.contains('\n') && value.trim() == value;
+        if block.len() > 1 || (block.len() == 1 && value.is_empty()) || !verbatim {
+            let etype = ErrorType::InvalidValue(block.name().to_string(), value);
+            return Err(unwritable(block, etype, VERBATIM));
+        }
+        if !value.is_empty() {
+            out.push(' ');
+            out.push_str(&value);
+        }
+        out.push('\n');
+    } else {
+        out.push(':');
+        for i in 0..block.len() {
+            out.push(' ');
+            out.push_str(&quote_value(block.get(i)));
+        }
+        out.push('\n');
+    }
+    Ok(())
+}
+
+fn unwritable(block: &ConfigBlock, etype: ErrorType, expected: &'static str) -> Error {
+    let (line, col) = block.name_span().map(|s| (s.line, s.col)).unwrap_or((0, 0));
+    Error::new(line, col, etype, Some(expected))
+}
+
+/// What `line_values` can write
+const VERBATIM: &str = "a single non-empty value without line breaks or surrounding whitespace";
+
+fn write_indent(out: &mut String, options: &FormatOptions, depth: usize) {
+    for _ in 0..depth {
+        match options.indent {
+            Indent::Tabs => out.push('\t'),
+            Indent::Spaces(n) => out.push_str(&" ".repeat(n)),
+        }
+    }
+}
+
+/// Quotes a value if the lexer would not read it back as a single raw literal
+pub fn quote(value: &str) -> String {
+    quote_with(value, true)
+}
+
+/// Like `quote`, but for the values of an option. Colons after the one that
+/// ends the key are read as part of the value, so `fe80::1` stays unquoted
+pub fn quote_value(value: &str) -> String {
+    quote_with(value, false)
+}
+
+fn quote_with(value: &str, colons: bool) -> String {
+    let needs_quotes = value.is_empty()
+        || value.contains("//")
+        || value.contains("/*")
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || "{}();\"\\#".contains(c) || (colons && c == ':'));
+    if !needs_quotes {
+        return value.to_string();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the block in corosync.conf syntax using the default `FormatOptions`.
+/// Names are written as they are, use `ConfigBlock::to_corosync_string` to
+/// have them checked
+impl fmt::Display for ConfigBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = write_tree(self, &FormatOptions::default()).map_err(|_| fmt::Error)?;
+        f.write_str(&text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn writes_sections_and_leaves() {
+        let block = ConfigBlock::new(
+            String::new(),
+            vec![],
+            vec![ConfigBlock::new(
+                String::from("totem"),
+                vec![],
+                vec![
+                    ConfigBlock::new(String::from("version"), vec![String::from("2")], vec![]),
+                    ConfigBlock::new(
+                        String::from("interface"),
+                        vec![],
+                        vec![ConfigBlock::new(
+                            String::from("linknumber"),
+                            vec![String::from("0")],
+                            vec![],
+                        )],
+                    ),
+                ],
+            )],
+        );
+        assert_eq!(
+            write(&block, &FormatOptions::default()).unwrap(),
+            "totem {\n\tversion: 2\n\tinterface {\n\t\tlinknumber: 0\n\t}\n}\n"
+        );
+        assert_eq!(
+            write(
+                &block,
+                &FormatOptions {
+                    indent: Indent::Spaces(4),
+                    ..FormatOptions::default()
+                }
+            )
+            .unwrap(),
+            "totem {\n    version: 2\n    interface {\n        linknumber: 0\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn quotes_values_which_need_it() {
+        assert_eq!(
+            quote("/var/log/cluster/corosync.log"),
+            "/var/log/cluster/corosync.log"
+        );
+        assert_eq!(quote("prod cluster"), "\"prod cluster\"");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(quote("a//b"), "\"a//b\"");
+        assert_eq!(quote("a\tb\r\n"), "\"a\\tb\\r\\n\"");
+        assert_eq!(quote("fe80::1"), "\"fe80::1\"");
+        assert_eq!(quote_value("fe80::1"), "fe80::1");
+    }
+}
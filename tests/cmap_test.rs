@@ -0,0 +1,82 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::ConfigBlock;
+
+const EXAMPLE: &str = "
+totem {
+    version: 2
+    cluster_name: prod
+    interface {
+        linknumber: 0
+        knet_transport: udp
+    }
+    interface {
+        linknumber: 1
+        knet_transport: sctp
+    }
+}
+logging {
+    to_syslog: yes
+    logger_subsys {
+        subsys: QUORUM
+        debug: off
+    }
+}
+nodelist {
+    node {
+        ring0_addr: fe80::1
+        nodeid: 1
+    }
+    node {
+        ring0_addr: fe80::2
+        nodeid: 2
+    }
+}
+quorum {
+    provider: corosync_votequorum
+}
+";
+
+#[test]
+fn test_to_cmap_uses_corosync_key_names() {
+    let cfg = corosync_config_parser::parse(EXAMPLE).unwrap();
+    let pairs = cfg.to_cmap();
+    let pairs: Vec<(&str, &str)> = pairs
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("totem.version", "2"),
+            ("totem.cluster_name", "prod"),
+            ("totem.interface.0.knet_transport", "udp"),
+            ("totem.interface.1.knet_transport", "sctp"),
+            ("logging.to_syslog", "yes"),
+            ("logging.logger_subsys.QUORUM.subsys", "QUORUM"),
+            ("logging.logger_subsys.QUORUM.debug", "off"),
+            ("nodelist.node.0.ring0_addr", "fe80::1"),
+            ("nodelist.node.0.nodeid", "1"),
+            ("nodelist.node.1.ring0_addr", "fe80::2"),
+            ("nodelist.node.1.nodeid", "2"),
+            ("quorum.provider", "corosync_votequorum"),
+        ]
+    );
+}
+
+#[test]
+fn test_from_cmap_rebuilds_the_tree() {
+    let cfg = corosync_config_parser::parse(EXAMPLE).unwrap();
+    let rebuilt = ConfigBlock::from_cmap(cfg.to_cmap());
+    assert_eq!(rebuilt, cfg);
+
+    // Keys may come in any order, e.g. sorted like corosync-cmapctl prints them
+    let mut pairs = cfg.to_cmap();
+    pairs.sort();
+    let rebuilt = ConfigBlock::from_cmap(pairs);
+    assert_eq!(
+        rebuilt.query("nodelist.node[nodeid=2].ring0_addr").unwrap()[0].value(),
+        Some("fe80::2")
+    );
+    assert_eq!(rebuilt.to_cmap().len(), cfg.to_cmap().len());
+}
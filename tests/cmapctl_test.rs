@@ -0,0 +1,64 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::cmapctl::{self, ValueType};
+use corosync_config_parser::{CmapDump, Difference};
+
+const FILE: &str = "totem {
+    token: 3000
+    cluster_name: prod
+}
+nodelist {
+    node {
+        ring0_addr: fe80::1
+        nodeid: 1
+    }
+}
+quorum {
+    provider: corosync_votequorum
+    two_node: 1
+}
+";
+
+const DUMP: &str = "nodelist.node.0.nodeid (u32) = 1
+nodelist.node.0.ring0_addr (str) = fe80::1
+quorum.provider (str) = corosync_votequorum
+runtime.config.totem.token (u32) = 3000
+totem.cluster_name (str) = test
+totem.token (u32) = 3000
+totem.version (u32) = 2
+";
+
+#[test]
+fn test_compare_file_with_runtime() {
+    let file = corosync_config_parser::parse(FILE).unwrap();
+    let dump = CmapDump::parse(DUMP).unwrap();
+    assert_eq!(dump.get("totem.token").unwrap().value_type, ValueType::U32);
+
+    let differences = cmapctl::compare(&file, &dump);
+    let keys: Vec<&str> = differences.iter().map(|d| d.key()).collect();
+    assert_eq!(
+        keys,
+        vec![
+            "totem.cluster_name",
+            "quorum.two_node",
+            "runtime.config.totem.token",
+            "totem.version",
+        ]
+    );
+    match differences[0] {
+        Difference::Changed {
+            ref file,
+            file_span,
+            ref runtime,
+            ..
+        } => {
+            assert_eq!(file, "prod");
+            assert_eq!(file_span.map(|s| (s.line, s.col)), Some((3, 5)));
+            assert_eq!(runtime.value, "test");
+            assert_eq!(runtime.value_span.line, 5);
+        }
+        ref other => panic!("expected a changed value, got {:?}", other),
+    }
+    assert!(matches!(differences[1], Difference::FileOnly { .. }));
+    assert!(matches!(differences[3], Difference::RuntimeOnly(_)));
+}
@@ -0,0 +1,60 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{diagnostic, parse_with_diagnostics, ParseOptions, Severity};
+
+#[test]
+fn test_parse_reports_warnings() {
+    let source = "totem {
+    token: 3000
+    token: 5000
+    interface {
+    }
+};
+quorum {
+    provider: corosync_votequorum
+}
+";
+    let (cfg, diagnostics) = parse_with_diagnostics(source, &ParseOptions::default()).unwrap();
+    assert_eq!(cfg, corosync_config_parser::parse(source).unwrap());
+
+    let found: Vec<(&str, u32, u32)> = diagnostics
+        .iter()
+        .map(|d| (d.code, d.span.line, d.span.col))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (diagnostic::DUPLICATE_KEY, 3, 5),
+            (diagnostic::EMPTY_SECTION, 4, 5),
+            (diagnostic::STRAY_SEMICOLON, 6, 2),
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    assert_eq!(diagnostics[1].message, "section `interface` is empty");
+    assert_eq!(
+        diagnostics[1].span.slice(source),
+        Some("interface {\n    }")
+    );
+}
+
+#[test]
+fn test_lenient_parse_reports_skipped_tokens() {
+    let options = ParseOptions {
+        strict: false,
+        ..ParseOptions::default()
+    };
+    let (_, diagnostics) = parse_with_diagnostics("token: 3000 ) 5000\n", &options).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, diagnostic::SKIPPED_TOKENS);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "warning[skipped-tokens]: skipped `)` and the rest of the option at line 1, column 13"
+    );
+}
+
+#[test]
+fn test_valid_config_has_no_diagnostics() {
+    let (_, diagnostics) =
+        parse_with_diagnostics("totem {\n    version: 2\n}\n", &ParseOptions::default()).unwrap();
+    assert!(diagnostics.is_empty());
+}
@@ -0,0 +1,100 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::ConfigBlock;
+
+const COROSYNC_EXAMPLE: &str = "
+    totem {
+        version: 2
+        token: 3000
+        interface {
+            linknumber: 0
+        }
+        interface {
+            linknumber: 1
+        }
+    }
+    nodelist {
+        node {
+            ring0_addr: 192.168.1.1
+            nodeid: 1
+        }
+    }
+";
+
+#[test]
+fn test_set_path_updates_existing_option() {
+    let mut cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    cfg.set_path(&["totem", "token"], "5000");
+    assert_eq!(cfg.path(vec!["totem", "token"]), Some("5000"));
+    assert_eq!(cfg.matching("totem").next().unwrap().inner().len(), 4);
+}
+
+#[test]
+fn test_set_path_creates_missing_sections() {
+    let mut cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    cfg.set_path(&["quorum", "two_node"], "1");
+    assert_eq!(cfg.path(vec!["quorum", "two_node"]), Some("1"));
+
+    let reparsed = corosync_config_parser::parse(&cfg.to_string()).unwrap();
+    assert_eq!(reparsed, cfg);
+}
+
+#[test]
+fn test_path_mut_and_params() {
+    let mut cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let addr = cfg.path_mut(&["nodelist", "node", "ring0_addr"]).unwrap();
+    addr.set_param(0, "10.0.0.1");
+    addr.set_param(1, "10.0.0.2");
+    assert_eq!(addr.len(), 2);
+    assert!(addr.param_span(1).is_none());
+
+    addr.params_mut().truncate(1);
+    assert_eq!(addr.param_span(0), None);
+    assert_eq!(
+        cfg.path(vec!["nodelist", "node", "ring0_addr"]),
+        Some("10.0.0.1")
+    );
+    assert!(cfg.path_mut(&["nodelist", "missing"]).is_none());
+}
+
+#[test]
+fn test_set_param_drops_stale_spans() {
+    let mut cfg = corosync_config_parser::parse("a: b c\n").unwrap();
+    let a = cfg.path_mut(&["a"]).unwrap();
+    a.set_param(0, "12345");
+    assert_eq!(a.param_span(0), None);
+    assert_eq!(a.param_span(1).unwrap().slice("a: b c\n"), Some("c"));
+    a.set_param(1, "12345");
+    assert_eq!(a.param_span(1), None);
+    assert_eq!(a.span().unwrap().end, 1);
+}
+
+#[test]
+fn test_remove_and_insert() {
+    let mut cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let totem = cfg.path_mut(&["totem"]).unwrap();
+
+    let removed = totem.remove_matching("interface");
+    assert_eq!(removed.len(), 2);
+    assert_eq!(
+        removed[1].matching("linknumber").next().unwrap().get(0),
+        "1"
+    );
+    assert_eq!(totem.matching("interface").count(), 0);
+
+    totem.insert_at(
+        0,
+        ConfigBlock::new(
+            String::from("cluster_name"),
+            vec![String::from("prod")],
+            vec![],
+        ),
+    );
+    assert_eq!(totem.inner()[0].name(), "cluster_name");
+    assert_eq!(totem.inner()[1].name(), "version");
+
+    for version in totem.matching_mut("version") {
+        version.set_param(0, "3");
+    }
+    assert_eq!(cfg.path(vec!["totem", "version"]), Some("3"));
+}
@@ -0,0 +1,81 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{CorosyncConfig, CorosyncVersion, Origin};
+
+#[test]
+fn test_fills_defaults_and_tags_origins() {
+    let cfg = corosync_config_parser::parse(
+        "totem {
+    token: 5000
+    token: 4000
+    cluster_name: prod
+}
+",
+    )
+    .unwrap();
+    let config = cfg.effective(CorosyncVersion::V3);
+
+    let token = config.get("totem.token").unwrap();
+    assert_eq!(token.value, "4000");
+    assert_eq!(token.origin, Origin::Explicit);
+    assert_eq!(token.span.unwrap().line, 3);
+
+    let found = |key: &str| {
+        let value = config.get(key).unwrap();
+        (value.value.as_str(), value.origin)
+    };
+    assert_eq!(found("totem.transport"), ("knet", Origin::Default));
+    assert_eq!(found("totem.ip_version"), ("ipv6-4", Origin::Default));
+    assert_eq!(found("totem.token_coefficient"), ("650", Origin::Default));
+    assert_eq!(found("totem.join"), ("50", Origin::Default));
+    assert_eq!(found("totem.max_messages"), ("17", Origin::Default));
+    assert_eq!(found("totem.consensus"), ("4800", Origin::Derived));
+    assert_eq!(found("totem.crypto_cipher"), ("none", Origin::Default));
+    assert_eq!(config.values()[0].key, "totem.token");
+    assert_eq!(config.values()[1].key, "totem.cluster_name");
+}
+
+#[test]
+fn test_defaults_depend_on_the_version() {
+    let cfg = corosync_config_parser::parse("totem {\n    secauth: on\n}\n").unwrap();
+    let config = cfg.effective(CorosyncVersion::V2);
+    assert_eq!(config.value("totem.transport"), Some("udp"));
+    assert_eq!(config.value("totem.ip_version"), Some("ipv4"));
+    assert_eq!(config.value("totem.token"), Some("1000"));
+    assert_eq!(config.value("totem.consensus"), Some("1200"));
+    let cipher = config.get("totem.crypto_cipher").unwrap();
+    assert_eq!(
+        (cipher.value.as_str(), cipher.origin),
+        ("aes256", Origin::Derived)
+    );
+
+    let config = cfg.effective(CorosyncVersion::V3);
+    assert_eq!(config.value("totem.token"), Some("3000"));
+    assert_eq!(config.value("totem.crypto_cipher"), Some("none"));
+}
+
+#[test]
+fn test_resolves_the_typed_model() {
+    let cfg = corosync_config_parser::parse("totem {\n    consensus: 9000\n}\n").unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+    let config = model.effective(CorosyncVersion::default());
+    let consensus = config.get("totem.consensus").unwrap();
+    assert_eq!(consensus.value, "9000");
+    assert_eq!(consensus.origin, Origin::Explicit);
+    assert_eq!(consensus.span, None);
+    assert_eq!(config.value("totem.token_retransmit"), Some("714"));
+}
+
+#[test]
+fn test_logging_defaults() {
+    let cfg = corosync_config_parser::parse("logging {\n    to_syslog: no\n}\n").unwrap();
+    let config = cfg.effective(CorosyncVersion::V3);
+    let found = |key: &str| {
+        let value = config.get(key).unwrap();
+        (value.value.as_str(), value.origin)
+    };
+    assert_eq!(found("logging.to_syslog"), ("no", Origin::Explicit));
+    assert_eq!(found("logging.to_stderr"), ("yes", Origin::Default));
+    assert_eq!(found("logging.to_logfile"), ("no", Origin::Default));
+    assert_eq!(found("logging.debug"), ("off", Origin::Default));
+}
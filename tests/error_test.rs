@@ -0,0 +1,42 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+use std::error::Error;
+
+#[test]
+fn test_error_display() {
+    let source = "totem {\n    token: 3000 )\n}";
+    let err = corosync_config_parser::parse(source).unwrap_err();
+
+    assert_eq!(err.line(), 2);
+    assert_eq!(err.col(), 17);
+    assert_eq!(err.expected(), Some("; or {"));
+    assert_eq!(
+        err.to_string(),
+        "unexpected `)` at line 2, column 17, expected ; or {"
+    );
+}
+
+#[test]
+fn test_error_render() {
+    let source = "totem {\n    token: 3000 )\n}";
+    let err = corosync_config_parser::parse(source).unwrap_err();
+
+    assert_eq!(
+        err.render(source),
+        "error: unexpected `)`\n \
+         --> 2:17\n  \
+         |\n\
+         2 |     token: 3000 )\n  \
+         |                 ^ expected ; or {\n"
+    );
+}
+
+#[test]
+fn test_error_is_std_error() {
+    let err: Box<dyn Error> = corosync_config_parser::parse("totem {").unwrap_err().into();
+    assert!(err.to_string().starts_with("unexpected end of file"));
+    assert!(err
+        .downcast_ref::<corosync_config_parser::ParseError>()
+        .is_some_and(|e| *e.error_type() == ErrorType::UnexpectedEOF));
+}
@@ -0,0 +1,89 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{BlockKind, ConfigBlock, ConfigNode};
+
+#[test]
+fn test_empty_sections_differ_from_options_without_value() {
+    let cfg =
+        corosync_config_parser::parse("empty {\n}\nflag:\nquorum {\n\tprovider: x\n}\n").unwrap();
+    let kinds: Vec<(&str, BlockKind)> = cfg.inner().iter().map(|b| (b.name(), b.kind())).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("empty", BlockKind::Section),
+            ("flag", BlockKind::Option),
+            ("quorum", BlockKind::Section),
+        ]
+    );
+
+    let sections: Vec<&str> = cfg.sections().map(|b| b.name()).collect();
+    assert_eq!(sections, vec!["empty", "quorum"]);
+    let options: Vec<&str> = cfg.options().map(|b| b.name()).collect();
+    assert_eq!(options, vec!["flag"]);
+
+    match cfg.inner()[2].as_node() {
+        ConfigNode::Section { name, children } => {
+            assert_eq!(name, "quorum");
+            assert_eq!(
+                children[0].as_node(),
+                ConfigNode::Option {
+                    key: "provider",
+                    value: Some("x")
+                }
+            );
+        }
+        node => panic!("expected a section, got {:?}", node),
+    }
+}
+
+#[test]
+fn test_constructors_set_the_kind() {
+    let empty = ConfigBlock::section(String::from("empty"), vec![], vec![]);
+    let flag = ConfigBlock::option(String::from("empty"), vec![]);
+    assert!(empty.is_section());
+    assert!(flag.is_option());
+    assert_ne!(empty, flag);
+    assert_eq!(empty.to_string(), "empty {\n}\n");
+    assert_eq!(flag.to_string(), "empty:\n");
+
+    // `new` infers the kind from the inner blocks it is given
+    let inferred = ConfigBlock::new(String::from("empty"), vec![], vec![]);
+    assert_eq!(inferred, flag);
+    let mut section = ConfigBlock::new(String::from("totem"), vec![], vec![flag]);
+    assert!(section.is_section());
+    section.inner_mut().clear();
+    assert_eq!(
+        section,
+        ConfigBlock::section(String::from("totem"), vec![], vec![])
+    );
+    section.set_kind(BlockKind::Option);
+    assert!(section.is_option());
+}
+
+#[test]
+fn test_sections_stay_sections_when_emptied() {
+    let mut cfg = ConfigBlock::new(
+        String::new(),
+        vec![],
+        vec![ConfigBlock::new(
+            String::from("quorum"),
+            vec![],
+            vec![ConfigBlock::option(
+                String::from("provider"),
+                vec![String::from("x")],
+            )],
+        )],
+    );
+    cfg.set_path(&["totem", "interface", "linknumber"], "0");
+    cfg.path_mut(&["quorum"]).unwrap().inner_mut().clear();
+    cfg.path_mut(&["totem", "interface"])
+        .unwrap()
+        .remove_matching("linknumber");
+
+    assert!(cfg.path_mut(&["quorum"]).unwrap().is_section());
+    assert!(cfg.path_mut(&["totem", "interface"]).unwrap().is_section());
+    assert_eq!(
+        cfg.to_string(),
+        "quorum {\n}\ntotem {\n\tinterface {\n\t}\n}\n"
+    );
+}
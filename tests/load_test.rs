@@ -0,0 +1,81 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!(
+        "corosync-config-parser-{}-{}",
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_parse_file() {
+    let path = temp_file("valid.conf", "totem {\n    token: 3000\n}\n");
+    let cfg = corosync_config_parser::parse_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(cfg.path(vec!["totem", "token"]), Some("3000"));
+}
+
+#[test]
+fn test_parse_file_syntax_error_carries_path() {
+    let path = temp_file("broken.conf", "totem {\n    token: 3000 )\n}\n");
+    let err = corosync_config_parser::parse_file(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(!err.is_io());
+    assert_eq!(err.path(), Some(path.as_path()));
+    assert_eq!((err.line(), err.col()), (2, 17));
+    assert!(err
+        .to_string()
+        .starts_with(&format!("{}: unexpected `)`", path.display())));
+}
+
+#[test]
+fn test_parse_file_io_error_carries_path() {
+    let path = env::temp_dir().join("corosync-config-parser-does-not-exist.conf");
+    let err = corosync_config_parser::parse_file(&path).unwrap_err();
+    assert!(err.is_io());
+    assert_eq!(err.path(), Some(path.as_path()));
+    match err.error_type() {
+        ErrorType::Io(kind, _) => assert_eq!(*kind, io::ErrorKind::NotFound),
+        other => panic!("unexpected error type {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_reader() {
+    let cfg = corosync_config_parser::parse_reader(
+        "quorum {\n    provider: corosync_votequorum\n}\n".as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(
+        cfg.path(vec!["quorum", "provider"]),
+        Some("corosync_votequorum")
+    );
+}
+
+#[test]
+fn test_default_config_path() {
+    env::remove_var(corosync_config_parser::CONFIG_FILE_ENV);
+    assert_eq!(
+        corosync_config_parser::default_config_path(),
+        PathBuf::from("/etc/corosync/corosync.conf")
+    );
+
+    env::set_var(
+        corosync_config_parser::CONFIG_FILE_ENV,
+        "/tmp/corosync.conf",
+    );
+    assert_eq!(
+        corosync_config_parser::default_config_path(),
+        PathBuf::from("/tmp/corosync.conf")
+    );
+    env::remove_var(corosync_config_parser::CONFIG_FILE_ENV);
+}
@@ -0,0 +1,144 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+use corosync_config_parser::model::{Address, CryptoCipher, DebugLevel, Transport};
+use corosync_config_parser::CorosyncConfig;
+
+const COROSYNC_EXAMPLE: &str = "
+    totem {
+        version: 2
+        cluster_name: hacluster
+        transport: knet
+        crypto_cipher: aes256
+        crypto_hash: sha256
+        token: 5000
+        future_option: 1
+        interface {
+            linknumber: 0
+            knet_ping_interval: 1000
+        }
+    }
+    nodelist {
+        node {
+            ring0_addr: 192.168.1.1
+            ring1_addr: node1.example.com
+            name: node1
+            nodeid: 1
+        }
+        node {
+            ring0_addr: 192.168.1.2
+            name: node2
+            nodeid: 2
+        }
+    }
+    quorum {
+        provider: corosync_votequorum
+        two_node: 1
+        device {
+            model: net
+        }
+    }
+    logging {
+        to_syslog: yes
+        timestamp: on
+        logger_subsys {
+            subsys: QUORUM
+            debug: trace
+        }
+    }
+    uidgid {
+        uid: hacluster
+    }
+    mystery {
+    }
+";
+
+#[test]
+fn test_typed_model() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+
+    let totem = model.totem.as_ref().unwrap();
+    assert_eq!(totem.version, Some(2));
+    assert_eq!(totem.transport, Some(Transport::Knet));
+    assert_eq!(totem.crypto_cipher, Some(CryptoCipher::Aes256));
+    assert_eq!(totem.token, Some(5000));
+    assert_eq!(totem.consensus, None);
+    assert_eq!(totem.interfaces.len(), 1);
+    assert_eq!(totem.interfaces[0].knet_ping_interval, Some(1000));
+
+    let nodes = &model.nodelist.as_ref().unwrap().nodes;
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0].nodeid, Some(1));
+    assert_eq!(
+        nodes[0].ring_addrs.get(&0),
+        Some(&Address::Ip("192.168.1.1".parse().unwrap()))
+    );
+    assert_eq!(
+        nodes[0].ring_addrs.get(&1),
+        Some(&Address::Hostname(String::from("node1.example.com")))
+    );
+
+    assert_eq!(model.quorum.as_ref().unwrap().two_node, Some(true));
+    let logging = model.logging.as_ref().unwrap();
+    assert_eq!(logging.to_syslog, Some(true));
+    assert_eq!(logging.logger_subsys[0].debug, Some(DebugLevel::Trace));
+    assert_eq!(model.uidgid[0].uid.as_deref(), Some("hacluster"));
+}
+
+#[test]
+fn test_unknown_keys_are_reported_and_kept() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+    assert_eq!(
+        model.unknown_keys(),
+        vec!["mystery", "totem.future_option", "quorum.device"]
+    );
+
+    let block = model.to_block();
+    assert_eq!(block.path(vec!["totem", "future_option"]), Some("1"));
+    assert_eq!(block.path(vec!["quorum", "device", "model"]), Some("net"));
+    assert_eq!(CorosyncConfig::from_block(&block).unwrap(), model);
+}
+
+#[test]
+fn test_round_trip_through_text() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+    let text = model.to_block().to_string();
+    let reparsed = corosync_config_parser::parse(&text).unwrap();
+    assert_eq!(CorosyncConfig::from_block(&reparsed).unwrap(), model);
+}
+
+#[test]
+fn test_repeated_sections_are_merged() {
+    let cfg = corosync_config_parser::parse(
+        "totem {\n    token: 1\n}\ntotem {\n    join: 2\n    token: 3\n}\n",
+    )
+    .unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+    let totem = model.totem.as_ref().unwrap();
+    assert_eq!(totem.token, Some(3));
+    assert_eq!(totem.join, Some(2));
+    assert!(model.unknown_keys().is_empty());
+}
+
+#[test]
+fn test_empty_round_trip() {
+    let cfg = corosync_config_parser::parse("").unwrap();
+    let model = CorosyncConfig::from_block(&cfg).unwrap();
+    assert_eq!(model.to_block(), cfg);
+}
+
+#[test]
+fn test_invalid_value_reports_location() {
+    let source = "totem {\n    token: soon\n}\n";
+    let cfg = corosync_config_parser::parse(source).unwrap();
+    let err = CorosyncConfig::from_block(&cfg).unwrap_err();
+    assert_eq!(
+        err.error_type(),
+        &ErrorType::InvalidValue(String::from("token"), String::from("soon"))
+    );
+    assert_eq!((err.line(), err.col()), (2, 12));
+    assert_eq!(err.expected(), Some("a number"));
+}
@@ -0,0 +1,204 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+use corosync_config_parser::{
+    parse_with, Comments, ConfigBlock, Duplicates, FormatOptions, ParseOptions,
+};
+
+#[test]
+fn test_lenient_mode_ends_options_at_unexpected_tokens() {
+    let source = "totem {\n    token: 3000 )\n    version: 2 }\n";
+    assert!(corosync_config_parser::parse(source).is_err());
+
+    let options = ParseOptions {
+        strict: false,
+        ..ParseOptions::default()
+    };
+    let cfg = parse_with(source, &options).unwrap();
+    assert_eq!(cfg.path(vec!["totem", "token"]), Some("3000"));
+    assert_eq!(cfg.path(vec!["totem", "version"]), Some("2"));
+}
+
+#[test]
+fn test_semicolons_can_be_rejected() {
+    let source = "totem { token: 3000 ; }\n";
+    assert!(corosync_config_parser::parse(source).is_ok());
+
+    let options = ParseOptions {
+        semicolons: false,
+        ..ParseOptions::default()
+    };
+    let err = parse_with(source, &options).unwrap_err();
+    assert_eq!((err.line(), err.col()), (1, 21));
+}
+
+#[test]
+fn test_parentheses_can_be_part_of_values() {
+    let source = "quorum {\n    provider: votequorum(v2)\n}\n";
+    assert!(corosync_config_parser::parse(source).is_err());
+
+    let options = ParseOptions {
+        parentheses: true,
+        ..ParseOptions::default()
+    };
+    let cfg = parse_with(source, &options).unwrap();
+    assert_eq!(cfg.path(vec!["quorum", "provider"]), Some("votequorum(v2)"));
+}
+
+#[test]
+fn test_comment_styles_can_be_disabled() {
+    let source = "logging {\n    # log to a file\n    logfile: /var/log//corosync.log\n}\n";
+    let options = ParseOptions {
+        comments: Comments {
+            hash: true,
+            double_slash: false,
+            block: false,
+            hash_only_at_line_start: false,
+        },
+        ..ParseOptions::default()
+    };
+    let cfg = parse_with(source, &options).unwrap();
+    assert_eq!(
+        cfg.path(vec!["logging", "logfile"]),
+        Some("/var/log//corosync.log")
+    );
+    assert_eq!(
+        corosync_config_parser::parse(source)
+            .unwrap()
+            .path(vec!["logging", "logfile"]),
+        Some("/var/log")
+    );
+}
+
+#[test]
+fn test_duplicate_key_policies() {
+    let source = "totem {\n    token: 1000\n    interface {\n    }\n    interface {\n    }\n    token: 3000\n}\n";
+    let parse = |duplicates| {
+        let options = ParseOptions {
+            duplicates,
+            ..ParseOptions::default()
+        };
+        parse_with(source, &options)
+    };
+
+    let cfg = parse(Duplicates::Keep).unwrap();
+    let tokens: Vec<&str> = cfg.inner()[0].matching("token").map(|t| t.get(0)).collect();
+    assert_eq!(tokens, vec!["1000", "3000"]);
+
+    let cfg = parse(Duplicates::First).unwrap();
+    let tokens: Vec<&str> = cfg.inner()[0].matching("token").map(|t| t.get(0)).collect();
+    assert_eq!(tokens, vec!["1000"]);
+    assert_eq!(cfg.inner()[0].matching("interface").count(), 2);
+
+    let cfg = parse(Duplicates::Last).unwrap();
+    assert_eq!(cfg.inner()[0].inner()[0].get(0), "3000");
+    assert_eq!(cfg.inner()[0].len(), 0);
+    assert_eq!(cfg.inner()[0].inner().len(), 3);
+
+    let err = parse(Duplicates::Error).unwrap_err();
+    assert_eq!(
+        err.error_type(),
+        &ErrorType::DuplicateKey(String::from("token"))
+    );
+    assert_eq!((err.line(), err.col()), (7, 5));
+}
+
+#[test]
+fn test_values_can_be_the_rest_of_the_line() {
+    let source = "totem {\n    cluster_name: prod cluster\n}\nlogging {\n    logfile: /var/log/my cluster/corosync.log # moved\n}\n";
+    assert_eq!(
+        corosync_config_parser::parse(source)
+            .unwrap()
+            .path(vec!["totem", "cluster_name"]),
+        Some("prod")
+    );
+
+    let options = ParseOptions {
+        line_values: true,
+        ..ParseOptions::default()
+    };
+    let cfg = parse_with(source, &options).unwrap();
+    assert_eq!(
+        cfg.path(vec!["totem", "cluster_name"]),
+        Some("prod cluster")
+    );
+    assert_eq!(
+        cfg.path(vec!["logging", "logfile"]),
+        Some("/var/log/my cluster/corosync.log")
+    );
+}
+
+#[test]
+fn test_corosync_comment_rules() {
+    let source = "# cluster config\nlogging {\n    # log to a file\n    logfile: /var/log//cluster/corosync.log\n    to_syslog: yes # not a comment\n}\n";
+    let cfg = corosync_config_parser::parse_corosync(source).unwrap();
+    assert_eq!(
+        cfg.path(vec!["logging", "logfile"]),
+        Some("/var/log//cluster/corosync.log")
+    );
+    assert_eq!(
+        cfg.path(vec!["logging", "to_syslog"]),
+        Some("yes # not a comment")
+    );
+
+    // C-style comments can still be asked for
+    let options = ParseOptions {
+        comments: Comments::default(),
+        ..ParseOptions::corosync()
+    };
+    let cfg = parse_with(source, &options).unwrap();
+    assert_eq!(cfg.path(vec!["logging", "logfile"]), Some("/var/log"));
+    assert_eq!(cfg.path(vec!["logging", "to_syslog"]), Some("yes"));
+}
+
+#[test]
+fn test_corosync_values_are_written_verbatim() {
+    let source = "logging {\n    logfile: /var/log//cluster/corosync.log\n    to_syslog: yes # not a comment\n}\ntotem {\n    cluster_name: \"prod\" cluster\n}\n";
+    let cfg = corosync_config_parser::parse_corosync(source).unwrap();
+    let written = cfg.to_corosync_string(&FormatOptions::corosync()).unwrap();
+    assert!(written.contains("\tlogfile: /var/log//cluster/corosync.log\n"));
+    assert_eq!(
+        corosync_config_parser::parse_corosync(&written).unwrap(),
+        cfg
+    );
+}
+
+#[test]
+fn test_line_values_with_line_breaks_are_rejected() {
+    let cfg = ConfigBlock::option(String::from("cluster_name"), vec![String::from("a\nb")]);
+    let err = cfg
+        .to_corosync_string(&FormatOptions::corosync())
+        .unwrap_err();
+    assert_eq!(
+        err.error_type(),
+        &ErrorType::InvalidValue(String::from("cluster_name"), String::from("a\nb"))
+    );
+}
+
+#[test]
+fn test_line_values_must_read_back_as_one_value() {
+    let option = |values: &[&str]| {
+        ConfigBlock::option(
+            String::from("cluster_name"),
+            values.iter().map(|v| v.to_string()).collect(),
+        )
+    };
+    for (values, joined) in &[(&["a", "b"][..], "a b"), (&[""][..], "")] {
+        let err = option(values)
+            .to_corosync_string(&FormatOptions::corosync())
+            .unwrap_err();
+        assert_eq!(
+            err.error_type(),
+            &ErrorType::InvalidValue(String::from("cluster_name"), joined.to_string())
+        );
+    }
+
+    let written = option(&[])
+        .to_corosync_string(&FormatOptions::corosync())
+        .unwrap();
+    assert_eq!(written, "cluster_name:\n");
+    assert_eq!(
+        corosync_config_parser::parse_corosync(&written).unwrap(),
+        ConfigBlock::new(String::new(), vec![], vec![option(&[])])
+    );
+}
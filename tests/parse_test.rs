@@ -1,3 +1,5 @@
+#![allow(clippy::iter_nth_zero)]
+
 extern crate corosync_config_parser;
 
 #[test]
@@ -19,7 +21,7 @@ fn test_parse_example_file() {
     "
     .to_string();
 
-    let cfg = corosync_config_parser::parse(corosync_example).unwrap();
+    let cfg = corosync_config_parser::parse(&corosync_example).unwrap();
     assert_eq!(cfg.name(), "");
     assert_eq!(cfg.len(), 0);
     let subsys = cfg
@@ -55,7 +57,160 @@ fn test_datapath_get() {
     "
     .to_string();
 
-    let cfg = corosync_config_parser::parse(corosync_example).unwrap();
+    let cfg = corosync_config_parser::parse(&corosync_example).unwrap();
     let subsys = cfg.path(vec!["logging", "logger_subsys", "subsys"]);
     assert_eq!(subsys, Some("QUORUM"));
 }
+
+#[test]
+fn test_block_spans() {
+    let corosync_example = "totem {\n    token: 3000\n}\n";
+
+    let cfg = corosync_config_parser::parse(corosync_example).unwrap();
+    let totem = cfg.matching("totem").next().unwrap();
+    let token = totem.matching("token").next().unwrap();
+
+    let name = token.name_span().unwrap();
+    assert_eq!((name.line, name.col), (2, 5));
+    assert_eq!(name.slice(corosync_example), Some("token"));
+    assert_eq!(
+        token.param_span(0).unwrap().slice(corosync_example),
+        Some("3000")
+    );
+    assert_eq!(token.param_span(1), None);
+
+    let open = totem.open_brace_span().unwrap();
+    let close = totem.close_brace_span().unwrap();
+    assert_eq!((open.start, close.start), (6, 24));
+    assert_eq!(
+        totem.span().unwrap().slice(corosync_example),
+        Some(&corosync_example[..25])
+    );
+}
+
+#[test]
+fn test_parse_bytes() {
+    let cfg = corosync_config_parser::parse_bytes(b"totem {\n    token: 3000\n}\n").unwrap();
+    assert_eq!(cfg.path(vec!["totem", "token"]), Some("3000"));
+}
+
+#[test]
+fn test_parse_bytes_rejects_invalid_utf8() {
+    let err = corosync_config_parser::parse_bytes(b"totem {\n    token: \xff\n}\n").unwrap_err();
+    assert_eq!(
+        err.error_type(),
+        &corosync_config_parser::error::ErrorType::InvalidUtf8
+    );
+    assert_eq!((err.line(), err.col()), (2, 12));
+}
+
+#[test]
+fn test_write_round_trip() {
+    let corosync_example = "
+        totem {
+            version: 2
+            cluster_name: \"prod cluster\"
+            crypto_cipher: none
+            interface {
+                linknumber: 0
+            }
+        }
+        logging {
+            logfile: /var/log/cluster/corosync.log
+            logger_subsys {
+                subsys: QUORUM
+                debug: off
+            }
+        }
+        quorum {
+        }
+    ";
+
+    let cfg = corosync_config_parser::parse(corosync_example).unwrap();
+    let written = cfg.to_string();
+    assert_eq!(corosync_config_parser::parse(&written).unwrap(), cfg);
+    assert!(written.contains("\tcluster_name: \"prod cluster\"\n"));
+    assert!(written.contains("quorum {\n}\n"));
+
+    let options = corosync_config_parser::FormatOptions {
+        indent: corosync_config_parser::Indent::Spaces(2),
+        ..Default::default()
+    };
+    let written = cfg.to_corosync_string(&options).unwrap();
+    assert_eq!(corosync_config_parser::parse(&written).unwrap(), cfg);
+    assert!(written.contains("\n    linknumber: 0\n"));
+}
+
+#[test]
+fn test_write_rejects_names_which_do_not_read_back() {
+    use corosync_config_parser::ConfigBlock;
+
+    let block = |name: &str| {
+        ConfigBlock::new(
+            String::new(),
+            vec![],
+            vec![ConfigBlock::section(
+                String::from("totem"),
+                vec![],
+                vec![ConfigBlock::option(
+                    name.to_string(),
+                    vec![String::from("1")],
+                )],
+            )],
+        )
+    };
+    let cfg = block("knet_mtu");
+    let written = cfg.to_corosync_string(&Default::default()).unwrap();
+    assert_eq!(corosync_config_parser::parse(&written).unwrap(), cfg);
+
+    for name in &["", "a b", "a:b", "a{", "}", "#a", "\"a\"", "a//b"] {
+        let err = block(name)
+            .to_corosync_string(&Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.error_type(),
+            &corosync_config_parser::error::ErrorType::Custom(format!(
+                "`{}` can not be written as a name",
+                name
+            ))
+        );
+    }
+}
+
+#[test]
+fn test_ipv6_nodelist() {
+    let corosync_example = "
+        totem {
+            bindnetaddr: 2001:db8::
+        }
+        nodelist {
+            node {
+                ring0_addr: fe80::1%eth0
+                ring1_addr:2001:db8::10
+                nodeid: 1
+            }
+            node {
+                ring0_addr: ::1
+                nodeid: 2
+            }
+        }
+    ";
+
+    let cfg = corosync_config_parser::parse(corosync_example).unwrap();
+    assert_eq!(cfg.path(vec!["totem", "bindnetaddr"]), Some("2001:db8::"));
+    let addrs: Vec<(&str, usize)> = cfg
+        .matching("nodelist")
+        .flat_map(|n| n.matching("node"))
+        .flat_map(|n| n.inner().iter())
+        .filter(|b| b.name().ends_with("_addr"))
+        .map(|b| (b.get(0), b.len()))
+        .collect();
+    assert_eq!(
+        addrs,
+        vec![("fe80::1%eth0", 1), ("2001:db8::10", 1), ("::1", 1)]
+    );
+
+    let written = cfg.to_string();
+    assert!(written.contains("ring0_addr: fe80::1%eth0\n"));
+    assert_eq!(corosync_config_parser::parse(&written).unwrap(), cfg);
+}
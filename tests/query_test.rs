@@ -0,0 +1,87 @@
+extern crate corosync_config_parser;
+
+const COROSYNC_EXAMPLE: &str = "
+    totem {
+        version: 2
+        interface {
+            linknumber: 0
+        }
+        interface {
+            linknumber: 1
+        }
+    }
+    nodelist {
+        node {
+            ring0_addr: 192.168.1.1
+            nodeid: 1
+        }
+        node {
+            ring0_addr: 192.168.1.2
+            nodeid: 2
+        }
+    }
+    logging {
+        logger_subsys {
+            subsys: QUORUM
+        }
+        logger_subsys {
+            subsys: CPG
+        }
+    }
+    quorum {
+        provider:
+    }
+";
+
+#[test]
+fn test_query_predicate() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let matches = cfg.query("nodelist.node[nodeid=2].ring0_addr").unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].value(), Some("192.168.1.2"));
+    assert_eq!(matches[0].location, vec![1, 1, 0]);
+    let span = matches[0].span().unwrap();
+    assert_eq!(
+        span.slice(COROSYNC_EXAMPLE),
+        Some("ring0_addr: 192.168.1.2")
+    );
+}
+
+#[test]
+fn test_query_index() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let matches = cfg.query("totem.interface[1].linknumber").unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].value(), Some("1"));
+    assert!(cfg
+        .query("totem.interface[2].linknumber")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_query_wildcards() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    let subsys: Vec<_> = cfg
+        .query("logging.logger_subsys[*].subsys")
+        .unwrap()
+        .iter()
+        .map(|m| m.value())
+        .collect();
+    assert_eq!(subsys, vec![Some("QUORUM"), Some("CPG")]);
+
+    assert_eq!(cfg.query("nodelist.node.*").unwrap().len(), 4);
+    assert_eq!(cfg.query("*").unwrap().len(), 4);
+}
+
+#[test]
+fn test_missing_values_do_not_panic() {
+    let cfg = corosync_config_parser::parse(COROSYNC_EXAMPLE).unwrap();
+    assert_eq!(cfg.path(vec!["quorum", "provider"]), None);
+    assert_eq!(cfg.path(vec!["quorum", "missing"]), None);
+
+    let matches = cfg.query("quorum.provider").unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].value(), None);
+    assert!(cfg.query("quorum.missing").unwrap().is_empty());
+}
@@ -0,0 +1,72 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+
+#[test]
+fn test_recovering_parse_collects_every_error() {
+    let source = "totem {
+    token: 3000 )
+    cluster_name: hacluster
+    ) consensus: 3600
+}
+quorum {
+    provider: corosync_votequorum (
+    two_node: 1
+}
+} logging {
+    to_syslog: yes
+}
+";
+    let (cfg, errors) = corosync_config_parser::parse_recovering(source);
+
+    let locations: Vec<(u32, u32)> = errors.iter().map(|e| (e.line(), e.col())).collect();
+    assert_eq!(locations, vec![(2, 17), (4, 5), (7, 35), (10, 1)]);
+
+    let totem = cfg.matching("totem").next().unwrap();
+    assert!(!totem.is_broken());
+    assert!(totem.matching("token").next().unwrap().is_broken());
+    assert_eq!(totem.path(vec!["token"]), Some("3000"));
+    assert_eq!(totem.path(vec!["cluster_name"]), Some("hacluster"));
+    assert_eq!(cfg.path(vec!["quorum", "two_node"]), Some("1"));
+    assert_eq!(cfg.path(vec!["logging", "to_syslog"]), Some("yes"));
+}
+
+#[test]
+fn test_recovering_parse_marks_unclosed_sections() {
+    let (cfg, errors) = corosync_config_parser::parse_recovering("totem {\n    interface {\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].error_type(), &ErrorType::UnexpectedEOF);
+
+    let totem = cfg.matching("totem").next().unwrap();
+    assert!(totem.is_broken());
+    assert!(totem.matching("interface").next().unwrap().is_broken());
+}
+
+#[test]
+fn test_recovering_parse_keeps_tokens_before_unterminated_string() {
+    let (cfg, errors) =
+        corosync_config_parser::parse_recovering("totem {\n    version: 2\n    cluster_name: \"a");
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].line(), errors[0].col()), (3, 20));
+    assert_eq!(cfg.path(vec!["totem", "version"]), Some("2"));
+}
+
+#[test]
+fn test_recovering_parse_of_valid_input_matches_parse() {
+    let source = "totem {\n    version: 2\n}\n";
+    let (cfg, errors) = corosync_config_parser::parse_recovering(source);
+    assert!(errors.is_empty());
+    assert_eq!(cfg, corosync_config_parser::parse(source).unwrap());
+}
+
+#[test]
+fn test_recovering_parse_lifts_sections_without_a_name() {
+    let (cfg, errors) =
+        corosync_config_parser::parse_recovering("{\n    a: b\n}\ntotem {\n    {\n");
+    assert_eq!(errors.len(), 3);
+    assert_eq!(cfg.path(vec!["a"]), Some("b"));
+
+    let written = cfg.to_string();
+    assert_eq!(written, "a: b\ntotem {\n}\n");
+    assert!(corosync_config_parser::parse(&written).is_ok());
+}
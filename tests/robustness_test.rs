@@ -0,0 +1,124 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::error::ErrorType;
+use corosync_config_parser::{Document, Duplicates, ParseOptions};
+
+const EXAMPLE: &str = "# corosync.conf
+totem {
+    version: 2
+    cluster_name: \"prod \\\"a\\\" \\u{e9}\"
+    token: 3000; /* block
+    comment */ crypto_cipher: aes256 // line comment
+    interface {
+        linknumber: 0
+    }
+}
+nodelist {
+    node {
+        ring0_addr: fe80::1
+        name: node(1)
+        nodeid: 1
+    }
+}
+";
+
+/// Small xorshift generator, so failures can be reproduced from the seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Runs every entry point which takes untrusted text. Any panic fails the test
+fn parse_everything(input: &str) {
+    let _ = corosync_config_parser::parse(input);
+    let _ = corosync_config_parser::parse_recovering(input);
+    let _ = Document::parse(input);
+    let lenient = ParseOptions {
+        strict: false,
+        parentheses: true,
+        semicolons: false,
+        ..ParseOptions::default()
+    };
+    let _ = corosync_config_parser::parse_with(input, &lenient);
+
+    let _ = corosync_config_parser::parse_corosync(input);
+    for duplicates in [
+        Duplicates::Keep,
+        Duplicates::First,
+        Duplicates::Last,
+        Duplicates::Error,
+    ] {
+        for base in [ParseOptions::default(), ParseOptions::corosync()] {
+            let options = ParseOptions { duplicates, ..base };
+            let _ = Document::parse_with(input, &options);
+        }
+    }
+    let corosync = ParseOptions::corosync();
+    let _ = corosync_config_parser::parse_with_diagnostics(input, &corosync);
+    let line_values = ParseOptions {
+        line_values: true,
+        ..ParseOptions::default()
+    };
+    let _ = corosync_config_parser::parse_with(input, &line_values);
+    let hash_comments = ParseOptions {
+        line_values: false,
+        ..ParseOptions::corosync()
+    };
+    let _ = corosync_config_parser::parse_with(input, &hash_comments);
+}
+
+#[test]
+fn test_no_panic_on_truncated_input() {
+    for (i, _) in EXAMPLE.char_indices() {
+        parse_everything(&EXAMPLE[..i]);
+        parse_everything(&EXAMPLE[i..]);
+    }
+}
+
+#[test]
+fn test_no_panic_on_random_input() {
+    let alphabet: Vec<char> = "ab :;{}()\"\\#/*\n\r\txu0{}é".chars().collect();
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20_000 {
+        let len = (rng.next() % 48) as usize;
+        let input: String = (0..len)
+            .map(|_| alphabet[(rng.next() % alphabet.len() as u64) as usize])
+            .collect();
+        parse_everything(&input);
+    }
+}
+
+#[test]
+fn test_no_panic_on_mutated_input() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let bytes = EXAMPLE.as_bytes();
+    for _ in 0..5_000 {
+        let mut mutated = bytes.to_vec();
+        for _ in 0..4 {
+            let i = (rng.next() % mutated.len() as u64) as usize;
+            mutated[i] = (rng.next() % 256) as u8;
+        }
+        let _ = corosync_config_parser::parse_bytes(&mutated);
+        parse_everything(&String::from_utf8_lossy(&mutated));
+    }
+}
+
+#[test]
+fn test_no_panic_on_deep_nesting() {
+    let deep = "a {\n".repeat(10_000);
+    parse_everything(&deep);
+    assert_eq!(
+        corosync_config_parser::parse(&deep)
+            .unwrap_err()
+            .error_type(),
+        &ErrorType::TooDeeplyNested
+    );
+    parse_everything(&"/*".repeat(1_000));
+    parse_everything(&"}".repeat(1_000));
+}
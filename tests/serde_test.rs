@@ -0,0 +1,230 @@
+#![cfg(feature = "serde")]
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{de, ser, FormatOptions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Config {
+    totem: Totem,
+    nodelist: Nodelist,
+    quorum: Option<Quorum>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Transport {
+    Udpu,
+    Knet,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Totem {
+    version: u8,
+    cluster_name: String,
+    transport: Transport,
+    secauth: Option<bool>,
+    #[serde(rename = "interface")]
+    interfaces: Vec<Interface>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Interface {
+    linknumber: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Nodelist {
+    node: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Node {
+    ring0_addr: String,
+    nodeid: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Quorum {
+    provider: String,
+    two_node: bool,
+}
+
+const COROSYNC_EXAMPLE: &str = "
+    totem {
+        version: 2
+        cluster_name: hacluster
+        transport: knet
+        secauth: on
+        interface {
+            linknumber: 0
+        }
+        interface {
+            linknumber: 1
+        }
+    }
+    nodelist {
+        node {
+            ring0_addr: 192.168.1.1
+            nodeid: 1
+        }
+    }
+    quorum {
+        provider: corosync_votequorum
+        two_node: 1
+    }
+";
+
+#[test]
+fn test_deserialize_example() {
+    let config: Config = de::from_str(COROSYNC_EXAMPLE).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            totem: Totem {
+                version: 2,
+                cluster_name: String::from("hacluster"),
+                transport: Transport::Knet,
+                secauth: Some(true),
+                interfaces: vec![Interface { linknumber: 0 }, Interface { linknumber: 1 }],
+            },
+            nodelist: Nodelist {
+                node: vec![Node {
+                    ring0_addr: String::from("192.168.1.1"),
+                    nodeid: 1,
+                }],
+            },
+            quorum: Some(Quorum {
+                provider: String::from("corosync_votequorum"),
+                two_node: true,
+            }),
+        }
+    );
+}
+
+#[test]
+fn test_deserialize_borrowed_and_maps() {
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize)]
+    struct Logging<'a> {
+        logfile: &'a str,
+        #[serde(flatten)]
+        rest: BTreeMap<String, String>,
+    }
+
+    let cfg = corosync_config_parser::parse(
+        "logging {\n    logfile: /var/log/corosync.log\n    to_syslog: yes\n}\n",
+    )
+    .unwrap();
+    let logging: BTreeMap<String, Logging> = de::from_block(&cfg).unwrap();
+    assert_eq!(logging["logging"].logfile, "/var/log/corosync.log");
+    assert_eq!(logging["logging"].rest["to_syslog"], "yes");
+}
+
+#[test]
+fn test_deserialize_error_location() {
+    let err = de::from_str::<Config>(&COROSYNC_EXAMPLE.replace("linknumber: 1", "linknumber: x"))
+        .unwrap_err();
+    assert_eq!((err.line(), err.col()), (11, 25));
+    assert_eq!(err.expected(), Some("a number"));
+
+    let err = de::from_str::<Config>(&COROSYNC_EXAMPLE.replace("nodeid: 1", "")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "missing field `nodeid` at line 15, column 9"
+    );
+}
+
+#[test]
+fn test_serialize_round_trip() {
+    let config: Config = de::from_str(COROSYNC_EXAMPLE).unwrap();
+    let text = ser::to_string(&config).unwrap();
+    assert_eq!(
+        text,
+        "totem {
+\tversion: 2
+\tcluster_name: hacluster
+\ttransport: knet
+\tsecauth: yes
+\tinterface {
+\t\tlinknumber: 0
+\t}
+\tinterface {
+\t\tlinknumber: 1
+\t}
+}
+nodelist {
+\tnode {
+\t\tring0_addr: 192.168.1.1
+\t\tnodeid: 1
+\t}
+}
+quorum {
+\tprovider: corosync_votequorum
+\ttwo_node: yes
+}
+"
+    );
+    assert_eq!(
+        corosync_config_parser::parse(&text).unwrap(),
+        ser::to_block(&config).unwrap()
+    );
+    assert_eq!(de::from_str::<Config>(&text).unwrap(), config);
+}
+
+#[test]
+fn test_serialize_quotes_values() {
+    #[derive(Serialize)]
+    struct Totem {
+        cluster_name: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        totem: Totem,
+    }
+
+    let config = Config {
+        totem: Totem {
+            cluster_name: "prod # {cluster}",
+        },
+    };
+    let text = ser::to_string(&config).unwrap();
+    assert_eq!(text, "totem {\n\tcluster_name: \"prod # {cluster}\"\n}\n");
+    assert_eq!(
+        corosync_config_parser::parse(&text).unwrap(),
+        ser::to_block(&config).unwrap()
+    );
+}
+
+#[test]
+fn test_serialize_for_corosync() {
+    #[derive(Serialize)]
+    struct Logging {
+        logfile: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        logging: Logging,
+    }
+
+    let config = Config {
+        logging: Logging {
+            logfile: "/var/log//x",
+        },
+    };
+    let block = ser::to_block(&config).unwrap();
+
+    let text = ser::to_string(&config).unwrap();
+    assert_eq!(text, "logging {\n\tlogfile: \"/var/log//x\"\n}\n");
+    assert_eq!(corosync_config_parser::parse(&text).unwrap(), block);
+
+    let text = ser::to_string_with(&config, &FormatOptions::corosync()).unwrap();
+    assert_eq!(text, "logging {\n\tlogfile: /var/log//x\n}\n");
+    assert_eq!(
+        corosync_config_parser::parse_corosync(&text).unwrap(),
+        block
+    );
+}
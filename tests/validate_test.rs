@@ -0,0 +1,95 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{validate, Severity};
+
+#[test]
+fn test_valid_config_has_no_diagnostics() {
+    let cfg = corosync_config_parser::parse(
+        "totem {
+    version: 2
+    cluster_name: prod
+    crypto_cipher: aes256
+    crypto_hash: sha256
+}
+nodelist {
+    node {
+        ring0_addr: 10.0.0.1
+        nodeid: 1
+    }
+    node {
+        ring0_addr: 10.0.0.2
+        nodeid: 2
+    }
+}
+quorum {
+    provider: corosync_votequorum
+}
+",
+    )
+    .unwrap();
+    assert_eq!(validate(&cfg), vec![]);
+}
+
+#[test]
+fn test_reports_what_corosync_would_reject() {
+    let cfg = corosync_config_parser::parse(
+        "totem {
+    token: 3s
+    transport: knet
+    crypto_cipher: aes256
+    interface {
+        bindnetaddr: 10.0.0.0
+    }
+}
+nodelist {
+    node {
+        nodeid: 0
+    }
+    node {
+        nodeid: 2
+    }
+    node {
+        nodeid: 2
+    }
+    node {
+        ring0_addr: 10.0.0.4
+    }
+}
+qourum {
+    provider: corosync_votequorum
+}
+",
+    )
+    .unwrap();
+    let found: Vec<(&str, Severity, u32, u32)> = validate(&cfg)
+        .iter()
+        .map(|d| (d.code, d.severity, d.span.line, d.span.col))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("invalid-value", Severity::Error, 2, 12),
+            ("crypto-mismatch", Severity::Error, 4, 20),
+            ("bindnetaddr-ignored", Severity::Warning, 6, 9),
+            ("invalid-nodeid", Severity::Error, 11, 17),
+            ("duplicate-nodeid", Severity::Error, 17, 17),
+            ("missing-nodeid", Severity::Error, 19, 5),
+            ("unknown-section", Severity::Warning, 23, 1),
+        ]
+    );
+}
+
+#[test]
+fn test_reports_a_missing_nodelist() {
+    let cfg = corosync_config_parser::parse("totem {\n    transport: udpu\n}\n").unwrap();
+    let diagnostics = validate(&cfg);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "missing-nodelist");
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "error[missing-nodelist]: transport `udpu` needs a nodelist with at least one node at line 2, column 16"
+    );
+
+    let cfg = corosync_config_parser::parse("totem {\n    transport: udp\n}\n").unwrap();
+    assert_eq!(validate(&cfg), vec![]);
+}
@@ -0,0 +1,84 @@
+extern crate corosync_config_parser;
+
+use corosync_config_parser::{visit, Entry, ParseOptions, Span, Visitor};
+use std::ops::ControlFlow;
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+
+impl Visitor for Recorder {
+    fn enter_section(&mut self, entry: &Entry, _open_brace: Span) -> ControlFlow<()> {
+        self.events
+            .push(format!("enter {} {:?}", entry.name, entry.params));
+        ControlFlow::Continue(())
+    }
+
+    fn option(&mut self, entry: &Entry) -> ControlFlow<()> {
+        self.events
+            .push(format!("option {} {:?}", entry.name, entry.params));
+        ControlFlow::Continue(())
+    }
+
+    fn exit_section(&mut self, close_brace: Option<Span>) -> ControlFlow<()> {
+        self.events
+            .push(format!("exit {}", close_brace.unwrap().line));
+        ControlFlow::Continue(())
+    }
+}
+
+#[test]
+fn test_visitor_receives_events_in_order() {
+    let source =
+        "totem {\n    version: 2\n    interface {\n        linknumber: 0\n    }\n}\nquorum {\n}\n";
+    let mut recorder = Recorder::default();
+    visit(source, &ParseOptions::default(), &mut recorder).unwrap();
+    assert_eq!(
+        recorder.events,
+        vec![
+            "enter totem []",
+            "option version [\"2\"]",
+            "enter interface []",
+            "option linknumber [\"0\"]",
+            "exit 5",
+            "exit 6",
+            "enter quorum []",
+            "exit 8",
+        ]
+    );
+}
+
+#[test]
+fn test_visitor_can_stop_early() {
+    struct ClusterName(Option<String>);
+
+    impl Visitor for ClusterName {
+        fn option(&mut self, entry: &Entry) -> ControlFlow<()> {
+            if entry.name != "cluster_name" {
+                return ControlFlow::Continue(());
+            }
+            self.0 = entry.value().map(String::from);
+            ControlFlow::Break(())
+        }
+    }
+
+    // Parsing stops before the syntax error at the end
+    let source = "totem {\n    cluster_name: hacluster\n    token: 3000 )\n";
+    let mut visitor = ClusterName(None);
+    visit(source, &ParseOptions::default(), &mut visitor).unwrap();
+    assert_eq!(visitor.0.as_deref(), Some("hacluster"));
+}
+
+#[test]
+fn test_visitor_stops_at_errors() {
+    let mut recorder = Recorder::default();
+    let err = visit(
+        "a: b\nc: d )\ne: f\n",
+        &ParseOptions::default(),
+        &mut recorder,
+    )
+    .unwrap_err();
+    assert_eq!((err.line(), err.col()), (2, 6));
+    assert_eq!(recorder.events, vec!["option a [\"b\"]"]);
+}